@@ -0,0 +1,148 @@
+use crate::token::{BasicToken, BASIC_35_KEYWORDS, BASIC_70_KEYWORDS, BASIC_V2_KEYWORDS};
+
+/// Keyword set of a BASIC version or extension, mapping the keyword names to
+/// their token bytes and back.
+///
+/// Implement it for extensions like Simons' BASIC that bring their own
+/// tokens, or build a `KeywordTable` of them.
+pub trait BasicDialect {
+    /// Keywords as `(name, token bytes)` in the order a tokenizer tries them,
+    /// names as `LIST` prints them.
+    fn keywords(&self) -> Box<dyn Iterator<Item = (&str, &[u8])> + '_>;
+
+    /// Token bytes of the keyword named `name`.
+    fn keyword_bytes(&self, name: &str) -> Option<&[u8]> {
+        self.keywords()
+            .find(|(keyword_name, _)| *keyword_name == name)
+            .map(|(_, bytes)| bytes)
+    }
+
+    /// Name of the keyword whose token `bytes` start with, and the length of
+    /// the token. The longest token wins.
+    fn keyword_name(&self, bytes: &[u8]) -> Option<(&str, usize)> {
+        self.keywords()
+            .filter(|(_, keyword_bytes)| bytes.starts_with(keyword_bytes))
+            .max_by_key(|(_, keyword_bytes)| keyword_bytes.len())
+            .map(|(name, keyword_bytes)| (name, keyword_bytes.len()))
+    }
+
+    /// First keyword `text` starts with, the way the screen editor tokenizes
+    /// it.
+    fn match_keyword(&self, text: &str) -> Option<(&str, &[u8])> {
+        self.keywords().find(|(name, _)| text.starts_with(name))
+    }
+
+    /// Token of the keyword named `name`.
+    fn keyword_token(&self, name: &str) -> Option<BasicToken> {
+        self.keyword_bytes(name).map(|bytes| BasicToken::Bytes(bytes.to_vec()))
+    }
+}
+
+/// Dialect backed by a list of keywords. Defaults to BASIC V2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordTable {
+    keywords: Vec<(String, Vec<u8>)>,
+}
+
+impl KeywordTable {
+    /// Keywords as `(name, token bytes)`, in the order a tokenizer tries them.
+    pub fn new(keywords: Vec<(String, Vec<u8>)>) -> Self {
+        Self { keywords }
+    }
+
+    /// BASIC V2 of the C64, VIC-20 and PET.
+    pub fn basic_v2() -> Self {
+        Self::new(
+            BASIC_V2_KEYWORDS
+                .iter()
+                .map(|(name, keyword)| (name.to_string(), vec![*keyword as u8]))
+                .collect(),
+        )
+    }
+
+    /// BASIC 3.5 of the C16 and Plus/4.
+    pub fn basic_35() -> Self {
+        let mut table = Self::basic_v2();
+        table.extend(
+            BASIC_35_KEYWORDS
+                .iter()
+                .map(|(name, keyword)| (name.to_string(), vec![*keyword as u8])),
+        );
+        table
+    }
+
+    /// BASIC 7.0 of the C128, BASIC 3.5 without `RLUM` plus the two-byte
+    /// keywords. These are tried first, as the ROM reads `DOPEN` as one
+    /// keyword rather than `DO` followed by `PEN`.
+    pub fn basic_70() -> Self {
+        let mut table = Self::basic_35();
+        table.keywords.retain(|(name, _)| name != "RLUM");
+        table.keywords.splice(
+            0..0,
+            BASIC_70_KEYWORDS
+                .iter()
+                .map(|(name, keyword)| (name.to_string(), (*keyword as u16).to_be_bytes().to_vec())),
+        );
+        table
+    }
+
+    /// Adds keywords, tried after the existing ones.
+    pub fn extend(&mut self, keywords: impl IntoIterator<Item = (String, Vec<u8>)>) {
+        self.keywords.extend(keywords);
+    }
+}
+
+impl Default for KeywordTable {
+    fn default() -> Self {
+        Self::basic_v2()
+    }
+}
+
+impl BasicDialect for KeywordTable {
+    fn keywords(&self) -> Box<dyn Iterator<Item = (&str, &[u8])> + '_> {
+        Box::new(
+            self.keywords
+                .iter()
+                .map(|(name, bytes)| (name.as_str(), bytes.as_slice())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::{BasicDialect, KeywordTable};
+    use crate::petscii::PetsciiEncodingOptions;
+
+    #[test]
+    pub fn test_keyword_table() {
+        let basic_v2 = KeywordTable::default();
+        assert_eq!(basic_v2.keyword_bytes("PRINT"), Some(&[0x99][..]));
+        assert_eq!(basic_v2.keyword_bytes("GRAPHIC"), None);
+        assert_eq!(basic_v2.keyword_name(&[0x99, 0x22]), Some(("PRINT", 1)));
+        assert_eq!(basic_v2.match_keyword("INPUT#1,A$"), Some(("INPUT#", &[0x84][..])));
+        assert_eq!(basic_v2.match_keyword("GOTO 10"), Some(("GOTO", &[0x89][..])));
+        assert_eq!(basic_v2.match_keyword("GO TO 10"), Some(("GO", &[0xCB][..])));
+        assert_eq!(basic_v2.match_keyword("X=1"), None);
+
+        let basic_35 = KeywordTable::basic_35();
+        assert_eq!(basic_35.keyword_bytes("RLUM"), Some(&[0xCE][..]));
+        assert_eq!(basic_35.keyword_name(&[0xCE, 0x02]), Some(("RLUM", 1)));
+
+        let basic_70 = KeywordTable::basic_70();
+        assert_eq!(basic_70.keyword_bytes("RLUM"), None);
+        assert_eq!(basic_70.keyword_bytes("GRAPHIC"), Some(&[0xDE][..]));
+        assert_eq!(basic_70.keyword_bytes("SLOW"), Some(&[0xFE, 0x26][..]));
+        assert_eq!(basic_70.keyword_name(&[0xCE, 0x02, 0x28]), Some(("POT", 2)));
+        assert_eq!(basic_70.keyword_name(&[0xFE, 0x20]), None);
+
+        let mut simons_basic = KeywordTable::default();
+        simons_basic.extend([(String::from("HIRES"), vec![0x64, 0x01])]);
+        assert_eq!(
+            simons_basic
+                .keyword_token("HIRES")
+                .map(|token| token.to_bytes(&PetsciiEncodingOptions::default())),
+            Some(vec![0x64, 0x01])
+        );
+        assert_eq!(simons_basic.keyword_name(&[0x64, 0x01]), Some(("HIRES", 2)));
+    }
+}
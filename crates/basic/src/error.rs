@@ -0,0 +1,52 @@
+use std::fmt::Display;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The requested line length is longer than `MAX_LINE_LENGTH`.
+    InvalidLineLength(usize),
+
+    /// The token doesn't fit into the remaining space of the line.
+    LineOverflow,
+
+    /// The input is not a well-formed tokenized BASIC program.
+    MalformedProgram,
+
+    /// The link of the line doesn't point to the line that follows it.
+    InvalidLinkPointer(u16),
+
+    /// A line in the range being replaced contains code, not only `DATA` or `REM`.
+    LineRangeOverlap(u16),
+
+    /// A replacement line is outside the replaced range or out of order.
+    InvalidLineNumber(u16),
+
+    /// The float is NaN, infinite or too large for the 5-byte format.
+    FloatOverflow,
+
+    /// A listing line can't be tokenized, with the 1-based line and column of
+    /// the problem.
+    ParseError {
+        line: usize,
+        column: usize,
+        reason: &'static str,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidLineLength(line_length) => write!(f, "invalid line length {}", line_length),
+            Error::LineOverflow => write!(f, "line overflow"),
+            Error::MalformedProgram => write!(f, "malformed BASIC program"),
+            Error::InvalidLinkPointer(line_number) => write!(f, "invalid link pointer in line {}", line_number),
+            Error::LineRangeOverlap(line_number) => write!(f, "line {} contains code", line_number),
+            Error::InvalidLineNumber(line_number) => write!(f, "invalid line number {}", line_number),
+            Error::FloatOverflow => write!(f, "float overflow"),
+            Error::ParseError { line, column, reason } => write!(f, "line {}, column {}: {}", line, column, reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
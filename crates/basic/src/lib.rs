@@ -1,7 +1,21 @@
+mod dialect;
+mod error;
+mod lister;
+mod machine;
+mod memory;
+mod mflpt;
 mod petscii;
 mod program;
 mod token;
+mod tokenizer;
 
+pub use dialect::{BasicDialect, KeywordTable};
+pub use error::{Error, Result};
+pub use machine::Machine;
+pub use memory::MemoryConfiguration;
+pub use mflpt::Mflpt;
 pub use petscii::{PetsciiEncodingOptions, PetsciiString, PetsciiVariant};
-pub use program::{BasicLine, BasicProgram, MAX_LINE_LENGTH};
-pub use token::{BasicKeyword, BasicToken};
+pub use program::{BasicLine, BasicProgram, BasicProgramWriter, MAX_LINE_LENGTH, MAX_LINE_NUMBER, MAX_STRING_LENGTH};
+pub use token::{
+    Basic35Keyword, Basic70Keyword, BasicKeyword, BasicToken, BASIC_35_KEYWORDS, BASIC_70_KEYWORDS, BASIC_V2_KEYWORDS,
+};
@@ -0,0 +1,100 @@
+use crate::dialect::BasicDialect;
+use crate::petscii::{petscii_char, PetsciiEncodingOptions};
+use crate::token::PI_TOKEN;
+
+const PETSCII_QUOTE: u8 = 0x22;
+
+/// Names of the control codes in listings, as `petcat` writes them.
+#[rustfmt::skip]
+const CONTROL_CODE_NAMES: &[(u8, &str)] = &[
+    (0x05, "wht"), (0x0D, "return"), (0x0E, "lower"), (0x11, "down"), (0x12, "rvon"), (0x13, "home"),
+    (0x14, "del"), (0x1C, "red"), (0x1D, "rght"), (0x1E, "grn"), (0x1F, "blu"),
+    (0x81, "orng"), (0x85, "f1"), (0x86, "f3"), (0x87, "f5"), (0x88, "f7"), (0x89, "f2"), (0x8A, "f4"),
+    (0x8B, "f6"), (0x8C, "f8"), (0x8D, "sret"), (0x8E, "upper"), (0x90, "blk"), (0x91, "up"), (0x92, "rvof"),
+    (0x93, "clr"), (0x94, "inst"), (0x95, "brn"), (0x96, "lred"), (0x97, "gry1"), (0x98, "gry2"), (0x99, "lgrn"),
+    (0x9A, "lblu"), (0x9B, "gry3"), (0x9C, "pur"), (0x9D, "left"), (0x9E, "yel"), (0x9F, "cyn"),
+];
+
+/// Lists the tokenized contents of a line the way `LIST` does: every byte
+/// from `$80` up outside quotes is expanded to its keyword, even after `REM`
+/// or `DATA`.
+///
+/// Characters without a Unicode counterpart are written in braces, control
+/// codes by name like `{clr}` and anything else by code like `{$a0}`.
+pub(crate) fn list_line(
+    line_bin: &[u8],
+    dialect: &dyn BasicDialect,
+    encoding_options: &PetsciiEncodingOptions,
+) -> String {
+    let mut text = String::new();
+    let mut quoted = false;
+    let mut pos = 0;
+
+    while pos < line_bin.len() {
+        let b = line_bin[pos];
+        if b == PETSCII_QUOTE {
+            quoted = !quoted;
+        }
+
+        if b == PI_TOKEN {
+            text.push('π');
+        } else if quoted || b < 0x80 {
+            push_char(&mut text, b, encoding_options);
+        } else if let Some((name, length)) = dialect.keyword_name(&line_bin[pos..]) {
+            text.push_str(name);
+            pos += length;
+            continue;
+        } else {
+            push_escape(&mut text, b);
+        }
+        pos += 1;
+    }
+
+    text
+}
+
+fn push_char(text: &mut String, b: u8, encoding_options: &PetsciiEncodingOptions) {
+    match petscii_char(b, encoding_options) {
+        Some(c) => text.push(c),
+        None => push_escape(text, b),
+    }
+}
+
+fn push_escape(text: &mut String, b: u8) {
+    match CONTROL_CODE_NAMES.iter().find(|(code, _)| *code == b) {
+        Some((_, name)) => text.push_str(&format!("{{{}}}", name)),
+        None => text.push_str(&format!("{{${:02x}}}", b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::KeywordTable;
+    use crate::lister::list_line;
+    use crate::petscii::{PetsciiEncodingOptions, PetsciiVariant};
+
+    #[test]
+    pub fn test_list_line() {
+        let unshifted = PetsciiEncodingOptions::default();
+        let shifted = PetsciiEncodingOptions {
+            variant: PetsciiVariant::Shifted,
+        };
+        let v2 = KeywordTable::basic_v2();
+
+        assert_eq!(
+            list_line(b"\x99 \"HI\":\x89 10", &v2, &unshifted),
+            "PRINT \"HI\":GOTO 10"
+        );
+        assert_eq!(
+            list_line(b"\x99 \"\x93\x05\x99\xa0\":\x8f \x99", &v2, &unshifted),
+            "PRINT \"{clr}{wht}{lgrn}{$a0}\":REM PRINT"
+        );
+        assert_eq!(list_line(b"\x99 \xff*2", &v2, &unshifted), "PRINT π*2");
+        assert_eq!(list_line(b"\x99 \"HI\xc8\"", &v2, &shifted), "PRINT \"hiH\"");
+        assert_eq!(list_line(b"\xcc 1", &v2, &unshifted), "{$cc} 1");
+        assert_eq!(
+            list_line(b"\xfe\x02 1", &KeywordTable::basic_70(), &unshifted),
+            "BANK 1"
+        );
+    }
+}
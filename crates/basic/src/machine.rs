@@ -0,0 +1,136 @@
+use crate::dialect::KeywordTable;
+use crate::memory::MemoryConfiguration;
+use crate::program::MAX_LINE_LENGTH;
+
+/// Commodore 8-bit machine a program is generated for.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Machine {
+    /// Commodore 64, BASIC V2.
+    #[default]
+    C64,
+
+    /// Unexpanded VIC-20. Not for one with only the 3K expansion, which moves
+    /// the start of BASIC down to `$0401`.
+    Vic20,
+
+    /// VIC-20 with 8K or more of expansion, which moves the start of BASIC to
+    /// `$1201`. Assumes 8K, the end can be raised for larger expansions.
+    Vic20Expanded,
+
+    /// PET/CBM with 32K of memory, BASIC 2 or 4.
+    Pet,
+
+    /// Commodore 16 with 16K of memory, BASIC 3.5.
+    C16,
+
+    /// Plus/4, BASIC 3.5.
+    Plus4,
+
+    /// Commodore 128 in native mode, BASIC 7.0. Variables live in bank 1, so
+    /// all of bank 0 up to `$FF00` holds the program text.
+    C128,
+}
+
+impl Machine {
+    /// Address BASIC programs are loaded to and run from.
+    pub fn load_address(self) -> u16 {
+        match self {
+            Machine::C64 => 0x0801,
+            Machine::Vic20 => 0x1001,
+            Machine::Vic20Expanded => 0x1201,
+            Machine::Pet => 0x0401,
+            Machine::C16 | Machine::Plus4 => 0x1001,
+            Machine::C128 => 0x1C01,
+        }
+    }
+
+    /// Memory available to BASIC programs on a stock machine.
+    pub fn memory(self) -> MemoryConfiguration {
+        let end = match self {
+            Machine::C64 => 0xA000,
+            Machine::Vic20 => 0x1E00,
+            Machine::Vic20Expanded => 0x4000,
+            Machine::Pet => 0x8000,
+            Machine::C16 => 0x4000,
+            Machine::Plus4 => 0xFD00,
+            Machine::C128 => 0xFF00,
+        };
+
+        MemoryConfiguration {
+            start: self.load_address(),
+            end,
+        }
+    }
+
+    /// Longest line written for the machine, counted in tokenized bytes. The
+    /// C64 takes `MAX_LINE_LENGTH`, the other machines are capped at the size
+    /// of their input buffer. Listed lines can be longer, as `LIST` expands
+    /// every token to its keyword.
+    pub fn max_line_length(self) -> usize {
+        match self {
+            Machine::C64 => MAX_LINE_LENGTH,
+            Machine::Vic20 | Machine::Vic20Expanded => 88,
+            Machine::Pet => 80,
+            Machine::C16 | Machine::Plus4 => 88,
+            Machine::C128 => 160,
+        }
+    }
+
+    /// Keywords of the BASIC version in ROM.
+    pub fn dialect(self) -> KeywordTable {
+        match self {
+            Machine::C64 | Machine::Vic20 | Machine::Vic20Expanded | Machine::Pet => KeywordTable::basic_v2(),
+            Machine::C16 | Machine::Plus4 => KeywordTable::basic_35(),
+            Machine::C128 => KeywordTable::basic_70(),
+        }
+    }
+
+    /// Zero page address of the pointer to the start of the program text
+    /// (`TXTTAB`).
+    pub fn basic_start_pointer(self) -> u16 {
+        match self {
+            Machine::Pet => 0x28,
+            Machine::C128 => 0x2D,
+            _ => 0x2B,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::{BasicDialect, KeywordTable};
+    use crate::machine::Machine;
+    use crate::program::MAX_LINE_LENGTH;
+
+    #[test]
+    pub fn test_memory() {
+        assert_eq!(Machine::C64.memory().size(), 38911);
+        assert_eq!(Machine::Vic20.memory().size(), 3583);
+        assert_eq!(Machine::Vic20Expanded.memory().size(), 11775);
+        assert_eq!(Machine::Pet.memory().size(), 31743);
+        assert_eq!(Machine::Plus4.memory().size(), 60671);
+
+        for machine in [
+            Machine::C64,
+            Machine::Vic20,
+            Machine::Vic20Expanded,
+            Machine::Pet,
+            Machine::C16,
+            Machine::Plus4,
+            Machine::C128,
+        ] {
+            assert_eq!(machine.memory().start, machine.load_address());
+            assert!(machine.memory().end > machine.load_address());
+            assert!(machine.max_line_length() <= MAX_LINE_LENGTH);
+        }
+
+        assert_eq!(Machine::C64.max_line_length(), MAX_LINE_LENGTH);
+        assert_eq!(Machine::Vic20.max_line_length(), 88);
+        assert_eq!(Machine::Pet.max_line_length(), 80);
+        assert_eq!(Machine::C128.max_line_length(), 160);
+
+        assert_eq!(Machine::C64.dialect(), KeywordTable::basic_v2());
+        assert_eq!(Machine::Plus4.dialect().keyword_bytes("RLUM"), Some(&[0xCE][..]));
+        assert_eq!(Machine::C128.dialect().keyword_bytes("RLUM"), None);
+    }
+}
@@ -0,0 +1,40 @@
+/// Memory a BASIC program and its variables live in, from the start of the
+/// program text up to the top of BASIC memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryConfiguration {
+    /// Address the program text starts at, the load address of BASIC programs.
+    pub start: u16,
+
+    /// First address past BASIC memory, where strings are allocated downwards
+    /// from.
+    pub end: u16,
+}
+
+impl MemoryConfiguration {
+    /// Stock C64, `38911 BASIC BYTES FREE` between `$0801` and `$A000`. See
+    /// `Machine::memory` for the other machines.
+    pub const C64: MemoryConfiguration = MemoryConfiguration {
+        start: 0x0801,
+        end: 0xA000,
+    };
+
+    /// Number of bytes available to the program and its variables.
+    pub fn size(&self) -> usize {
+        (self.end as usize).saturating_sub(self.start as usize)
+    }
+
+    /// Number of bytes from `load_address` up to the end of BASIC memory, or
+    /// `None` if a program loaded there would start below `start`.
+    pub fn available_from(&self, load_address: u16) -> Option<usize> {
+        if load_address < self.start {
+            return None;
+        }
+        Some((self.end as usize).saturating_sub(load_address as usize))
+    }
+}
+
+impl Default for MemoryConfiguration {
+    fn default() -> Self {
+        Self::C64
+    }
+}
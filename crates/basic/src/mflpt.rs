@@ -0,0 +1,172 @@
+use crate::error::{Error, Result};
+
+/// Number in the 5-byte packed format Commodore BASIC keeps variables and
+/// constants in (MFLPT).
+///
+/// The first byte is the exponent biased by 128, zero meaning the number is
+/// zero. It is followed by a big-endian 32-bit mantissa in `[0.5, 1)`, whose
+/// always-set top bit is replaced by the sign.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mflpt(pub [u8; 5]);
+
+impl Mflpt {
+    pub const ZERO: Mflpt = Mflpt([0x00; 5]);
+
+    /// Largest magnitude of a 5-byte float, just below 2^127.
+    pub const MAX: f64 = 1.7014118342085504e38;
+
+    /// Smallest positive magnitude of a 5-byte float, 2^-128.
+    pub const MIN_POSITIVE: f64 = 2.938735877055719e-39;
+
+    /// Converts a float the way the BASIC ROM rounds its floating point
+    /// accumulator: to the nearest mantissa, halves away from zero.
+    ///
+    /// Magnitudes that round to less than `MIN_POSITIVE` underflow to zero, as
+    /// in the ROM. Fails with `Error::FloatOverflow` on NaN, infinities and
+    /// magnitudes that round past `MAX` (`?OVERFLOW ERROR`).
+    pub fn from_f64(v: f64) -> Result<Self> {
+        if !v.is_finite() {
+            return Err(Error::FloatOverflow);
+        }
+
+        if v == 0.0 {
+            return Ok(Self::ZERO);
+        }
+
+        // `v.abs()` is `f * 2^exponent` with `f` in `[0.5, 1)`.
+        let (f, mut exponent) = frexp(v.abs());
+        let mut mantissa = (f * 2f64.powi(32)).round() as u64;
+        if mantissa == 1 << 32 {
+            mantissa >>= 1;
+            exponent += 1;
+        }
+
+        let biased_exponent = exponent + 128;
+        if biased_exponent > 0xFF {
+            return Err(Error::FloatOverflow);
+        }
+        if biased_exponent < 0x01 {
+            return Ok(Self::ZERO);
+        }
+
+        let sign = if v.is_sign_negative() { 0x80 } else { 0x00 };
+        Ok(Self([
+            biased_exponent as u8,
+            ((mantissa >> 24) as u8 & 0x7F) | sign,
+            (mantissa >> 16) as u8,
+            (mantissa >> 8) as u8,
+            mantissa as u8,
+        ]))
+    }
+
+    /// Converts back to a float. Every 5-byte float is exactly representable
+    /// as an `f64`.
+    pub fn to_f64(self) -> f64 {
+        let [exponent, m0, m1, m2, m3] = self.0;
+        if exponent == 0 {
+            return 0.0;
+        }
+
+        let mantissa = u32::from_be_bytes([m0 | 0x80, m1, m2, m3]);
+        let v = mantissa as f64 * 2f64.powi(exponent as i32 - 128 - 32);
+        if m0 & 0x80 != 0 {
+            -v
+        } else {
+            v
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 5] {
+        self.0
+    }
+}
+
+/// Splits a positive finite float into a fraction in `[0.5, 1)` and a power of
+/// two. The scaling is exact, including for subnormals.
+fn frexp(v: f64) -> (f64, i32) {
+    let (v, offset) = if v < f64::MIN_POSITIVE {
+        (v * 2f64.powi(64), -64)
+    } else {
+        (v, 0)
+    };
+
+    let exponent = ((v.to_bits() >> 52) & 0x7FF) as i32 - 1022;
+    let fraction = f64::from_bits((v.to_bits() & !(0x7FF << 52)) | (1022 << 52));
+    (fraction, exponent + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::mflpt::Mflpt;
+
+    #[test]
+    pub fn test_from_f64() {
+        let encode = |v| Mflpt::from_f64(v).unwrap().to_bytes();
+
+        assert_eq!(encode(0.0), [0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(-0.0), [0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(1.0), [0x81, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(-1.0), [0x81, 0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(0.5), [0x80, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(10.0), [0x84, 0x20, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(0.1), [0x7D, 0x4C, 0xCC, 0xCC, 0xCD]);
+        assert_eq!(encode(std::f64::consts::PI), [0x82, 0x49, 0x0F, 0xDA, 0xA2]);
+        assert_eq!(encode(-32768.0), [0x90, 0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(1e9), [0x9E, 0x6E, 0x6B, 0x28, 0x00]);
+        assert_eq!(encode(Mflpt::MAX), [0xFF, 0x7F, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(encode(Mflpt::MIN_POSITIVE), [0x01, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    pub fn test_rounding() {
+        let encode = |v| Mflpt::from_f64(v).unwrap().to_bytes();
+        let half_ulp = 2f64.powi(-32);
+
+        assert_eq!(encode(1.0 + half_ulp), [0x81, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(encode(-1.0 - half_ulp), [0x81, 0x80, 0x00, 0x00, 0x01]);
+        assert_eq!(encode(1.0 + half_ulp * 0.99), [0x81, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(1.0 - half_ulp / 4.0), [0x81, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(2.0 - half_ulp), [0x82, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    pub fn test_limits() {
+        assert_eq!(Mflpt::from_f64(f64::NAN), Err(Error::FloatOverflow));
+        assert_eq!(Mflpt::from_f64(f64::INFINITY), Err(Error::FloatOverflow));
+        assert_eq!(Mflpt::from_f64(2f64.powi(127)), Err(Error::FloatOverflow));
+        assert_eq!(Mflpt::from_f64(-1.8e38), Err(Error::FloatOverflow));
+
+        assert_eq!(Mflpt::from_f64(Mflpt::MIN_POSITIVE / 2.0), Ok(Mflpt::ZERO));
+        assert_eq!(Mflpt::from_f64(-1e-300), Ok(Mflpt::ZERO));
+        assert_eq!(Mflpt::from_f64(f64::MIN_POSITIVE / 4.0), Ok(Mflpt::ZERO));
+        assert_eq!(
+            Mflpt::from_f64(Mflpt::MIN_POSITIVE * (1.0 - 2f64.powi(-34))),
+            Ok(Mflpt([0x01, 0x00, 0x00, 0x00, 0x00]))
+        );
+    }
+
+    #[test]
+    pub fn test_to_f64() {
+        for v in [
+            0.0,
+            1.0,
+            -1.0,
+            0.75,
+            1868.0,
+            -35.7642,
+            123456789.0,
+            1e-30,
+            Mflpt::MAX,
+            -Mflpt::MAX,
+            Mflpt::MIN_POSITIVE,
+        ] {
+            let mflpt = Mflpt::from_f64(v).unwrap();
+            assert_eq!(Mflpt::from_f64(mflpt.to_f64()), Ok(mflpt));
+            assert!((mflpt.to_f64() - v).abs() <= v.abs() * 2f64.powi(-32));
+        }
+
+        assert_eq!(Mflpt([0x82, 0x49, 0x0F, 0xDA, 0xA2]).to_f64(), 3.1415926534682512);
+        assert_eq!(Mflpt([0x00, 0x12, 0x34, 0x56, 0x78]).to_f64(), 0.0);
+    }
+}
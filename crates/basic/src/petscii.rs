@@ -56,6 +56,33 @@ lazy_static! {
         ('P',  0xD0), ('Q',  0xD1), ('R',  0xD2), ('S',  0xD3), ('T',  0xD4), ('U',  0xD5), ('V',  0xD6), ('W',  0xD7),
         ('X',  0xD8), ('Y',  0xD9), ('Z',  0xDA),
     ]);
+
+    static ref PETSCII_UNSHIFTED_DECODING_MAP: HashMap<u8, char> = decoding_map(&PETSCII_UNSHIFTED_MAP);
+
+    static ref PETSCII_SHIFTED_DECODING_MAP: HashMap<u8, char> = decoding_map(&PETSCII_SHIFTED_MAP);
+}
+
+/// Inverts an encoding map. Where several characters share a code, the one
+/// with the lowest code point wins, so the unshifted set decodes to uppercase.
+fn decoding_map(petscii_map: &HashMap<char, u8>) -> HashMap<u8, char> {
+    let mut entries = petscii_map.iter().collect::<Vec<_>>();
+    entries.sort();
+
+    let mut decoding_map = HashMap::new();
+    for (c, b) in entries {
+        decoding_map.entry(*b).or_insert(*c);
+    }
+    decoding_map
+}
+
+/// Character a printable PETSCII code stands for, if it has one.
+pub(crate) fn petscii_char(b: u8, encoding_options: &PetsciiEncodingOptions) -> Option<char> {
+    let decoding_map: &HashMap<_, _> = match encoding_options.variant {
+        PetsciiVariant::Unshifted => &PETSCII_UNSHIFTED_DECODING_MAP,
+        PetsciiVariant::Shifted => &PETSCII_SHIFTED_DECODING_MAP,
+    };
+
+    decoding_map.get(&b).copied()
 }
 
 impl PetsciiString {
@@ -70,4 +97,101 @@ impl PetsciiString {
             .map(|c| *petscii_map.get(&c).unwrap_or(&0x3F))
             .collect::<Vec<u8>>()
     }
+
+    /// Decodes PETSCII using the same tables as `to_petscii`, so every
+    /// encoded string decodes back to itself, apart from lowercase letters
+    /// coming back uppercase from the unshifted set. Codes without a Unicode
+    /// counterpart decode to `?`.
+    pub fn from_petscii(bytes: &[u8], encoding_options: &PetsciiEncodingOptions) -> Self {
+        Self(
+            bytes
+                .iter()
+                .map(|b| petscii_char(*b, encoding_options).unwrap_or('?'))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+
+    use crate::petscii::{
+        PetsciiEncodingOptions, PetsciiString, PetsciiVariant, PETSCII_SHIFTED_MAP, PETSCII_UNSHIFTED_MAP,
+    };
+
+    const UNSHIFTED: PetsciiEncodingOptions = PetsciiEncodingOptions {
+        variant: PetsciiVariant::Unshifted,
+    };
+
+    const SHIFTED: PetsciiEncodingOptions = PetsciiEncodingOptions {
+        variant: PetsciiVariant::Shifted,
+    };
+
+    fn petscii_map(encoding_options: &PetsciiEncodingOptions) -> &'static HashMap<char, u8> {
+        match encoding_options.variant {
+            PetsciiVariant::Unshifted => &PETSCII_UNSHIFTED_MAP,
+            PetsciiVariant::Shifted => &PETSCII_SHIFTED_MAP,
+        }
+    }
+
+    fn mappable_chars(encoding_options: &PetsciiEncodingOptions) -> Vec<char> {
+        let mut chars = petscii_map(encoding_options).keys().copied().collect::<Vec<char>>();
+        chars.sort();
+        chars
+    }
+
+    fn round_trip(string: &str, encoding_options: &PetsciiEncodingOptions) -> String {
+        let bytes = PetsciiString::from(string).to_petscii(encoding_options);
+        PetsciiString::from_petscii(&bytes, encoding_options).0
+    }
+
+    #[test]
+    pub fn test_from_petscii() {
+        assert_eq!(PetsciiString::from_petscii(b"HELLO \x5c\x7e", &UNSHIFTED).0, "HELLO £π");
+        assert_eq!(PetsciiString::from_petscii(b"HELLO \xd7", &SHIFTED).0, "hello W");
+        assert_eq!(PetsciiString::from_petscii(b"\x93\xa0", &UNSHIFTED).0, "??");
+
+        for encoding_options in [UNSHIFTED, SHIFTED] {
+            for c in mappable_chars(&encoding_options) {
+                let expected = match encoding_options.variant {
+                    PetsciiVariant::Unshifted => c.to_ascii_uppercase(),
+                    PetsciiVariant::Shifted => c,
+                };
+                assert_eq!(round_trip(&c.to_string(), &encoding_options), expected.to_string());
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_unshifted_round_trip(chars in vec(select(mappable_chars(&UNSHIFTED)), 0..100)) {
+            let string = chars.into_iter().collect::<String>();
+            prop_assert_eq!(round_trip(&string, &UNSHIFTED), string.to_ascii_uppercase());
+        }
+
+        #[test]
+        fn test_shifted_round_trip(chars in vec(select(mappable_chars(&SHIFTED)), 0..100)) {
+            let string = chars.into_iter().collect::<String>();
+            prop_assert_eq!(round_trip(&string, &SHIFTED), string);
+        }
+
+        #[test]
+        fn test_petscii_round_trip(bytes in vec(any::<u8>(), 0..100)) {
+            for encoding_options in [UNSHIFTED, SHIFTED] {
+                let decoded = PetsciiString::from_petscii(&bytes, &encoding_options);
+                // Codes missing from the encoding table decode to `?`
+                let petscii_map = petscii_map(&encoding_options);
+                let expected = bytes
+                    .iter()
+                    .map(|b| if petscii_map.values().any(|v| v == b) { *b } else { 0x3F })
+                    .collect::<Vec<u8>>();
+                prop_assert_eq!(decoded.to_petscii(&encoding_options), expected);
+            }
+        }
+    }
 }
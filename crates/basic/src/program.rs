@@ -1,8 +1,20 @@
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use crate::dialect::{BasicDialect, KeywordTable};
+use crate::error::{Error, Result};
+use crate::lister::list_line;
+use crate::memory::MemoryConfiguration;
 use crate::petscii::PetsciiEncodingOptions;
 use crate::token::BasicToken;
+use crate::tokenizer::tokenize_listing;
 
 pub const MAX_LINE_LENGTH: usize = 250;
 
+pub const MAX_LINE_NUMBER: u16 = 63999;
+
+pub const MAX_STRING_LENGTH: usize = 255;
+
 #[derive(Debug, Clone)]
 pub struct BasicLine(pub u16, pub Vec<BasicToken>);
 
@@ -11,15 +23,56 @@ impl BasicLine {
         self.1.iter().map(BasicToken::size).sum()
     }
 
-    pub fn push_token(&mut self, token: BasicToken, line_length: usize) -> Result<(), &str> {
-        assert!(line_length <= MAX_LINE_LENGTH);
+    pub fn push_token(&mut self, token: BasicToken, line_length: usize) -> Result<()> {
+        if line_length > MAX_LINE_LENGTH {
+            return Err(Error::InvalidLineLength(line_length));
+        }
 
         if token.size() + self.size() <= line_length {
             self.1.push(token);
             Ok(())
         } else {
-            Err("Line overflow")
+            Err(Error::LineOverflow)
+        }
+    }
+
+    /// Number of bytes the line takes up in memory: the link, the line number,
+    /// the tokens and the terminating zero.
+    pub fn memory_size(&self) -> usize {
+        self.size() + 5
+    }
+
+    /// Whether any statement of the line is something besides `DATA` or a
+    /// remark, with the keywords of `dialect`. Statements are split on `:`
+    /// outside quotes, up to the first `REM`.
+    pub fn is_code(&self, dialect: &dyn BasicDialect) -> bool {
+        let line_bin: Vec<u8> = self
+            .1
+            .iter()
+            .flat_map(|t| t.to_bytes(&PetsciiEncodingOptions::default()))
+            .collect();
+        let is_keyword = |bytes: &[u8], name: &str| dialect.keyword_bytes(name).is_some_and(|k| bytes.starts_with(k));
+
+        let mut statement_start = true;
+        let mut quoted = false;
+        for (pos, &b) in line_bin.iter().enumerate() {
+            if statement_start {
+                match b {
+                    b' ' | b':' => {}
+                    _ if is_keyword(&line_bin[pos..], "REM") => return false,
+                    _ if is_keyword(&line_bin[pos..], "DATA") => statement_start = false,
+                    _ => return true,
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => quoted = !quoted,
+                b':' if !quoted => statement_start = true,
+                _ => {}
+            }
         }
+        false
     }
 }
 
@@ -27,38 +80,224 @@ impl BasicLine {
 pub struct BasicProgram {
     pub load_address: u16,
     pub encoding_options: PetsciiEncodingOptions,
+    dialect: KeywordTable,
     pub contents: Vec<BasicLine>,
 }
 
 impl BasicProgram {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut prg_bin = vec![];
-        prg_bin.extend(self.load_address.to_le_bytes());
+    /// BASIC V2 program made of `contents`.
+    pub fn new(load_address: u16, encoding_options: PetsciiEncodingOptions, contents: Vec<BasicLine>) -> Self {
+        Self {
+            load_address,
+            encoding_options,
+            dialect: KeywordTable::default(),
+            contents,
+        }
+    }
 
-        let mut next_line_address = self.load_address;
+    /// Uses the keywords of `dialect` for the program.
+    pub fn with_dialect(mut self, dialect: KeywordTable) -> Self {
+        self.dialect = dialect;
+        self
+    }
 
-        for BasicLine(line_number, line_tokens) in &self.contents {
-            let mut line_bin = vec![];
-            line_bin.extend(line_number.to_le_bytes());
-            line_bin.extend(line_tokens.iter().flat_map(|t| t.to_bytes(&self.encoding_options)));
-            line_bin.push(0x00);
+    /// Keywords the program is tokenized with.
+    pub fn dialect(&self) -> &KeywordTable {
+        &self.dialect
+    }
 
-            next_line_address += line_bin.len() as u16 + 0x02;
+    /// Loads a tokenized BASIC V2 program, keeping the contents of each line as
+    /// is. Use `with_dialect` to list programs of other BASIC versions.
+    ///
+    /// Every line must link to the address the next line is loaded at.
+    pub fn from_bytes(prg_bin: &[u8], encoding_options: PetsciiEncodingOptions) -> Result<Self> {
+        let (load_address, mut rest) = match prg_bin {
+            [lo, hi, rest @ ..] => (u16::from_le_bytes([*lo, *hi]), rest),
+            _ => return Err(Error::MalformedProgram),
+        };
 
-            prg_bin.extend(next_line_address.to_le_bytes());
-            prg_bin.extend(line_bin);
+        let mut contents = vec![];
+        let mut line_address = load_address as usize;
+        loop {
+            match rest {
+                [0x00, 0x00, ..] => break,
+                [link_lo, link_hi, line_lo, line_hi, line_contents @ ..] => {
+                    let line_length = line_contents
+                        .iter()
+                        .position(|&b| b == 0x00)
+                        .ok_or(Error::MalformedProgram)?;
+                    let line_number = u16::from_le_bytes([*line_lo, *line_hi]);
+
+                    line_address += line_length + 5;
+                    if u16::from_le_bytes([*link_lo, *link_hi]) as usize != line_address {
+                        return Err(Error::InvalidLinkPointer(line_number));
+                    }
+
+                    contents.push(BasicLine(
+                        line_number,
+                        vec![BasicToken::Bytes(line_contents[..line_length].to_vec())],
+                    ));
+                    rest = &line_contents[line_length + 1..];
+                }
+                _ => return Err(Error::MalformedProgram),
+            }
         }
 
-        prg_bin.extend(0x0000u16.to_le_bytes());
-        prg_bin
+        Ok(Self::new(load_address, encoding_options, contents))
+    }
+
+    /// Tokenizes a plain-text BASIC V2 listing such as `10 PRINT "HI":GOTO 10`
+    /// the way the screen editor does, into a program loaded at `$0801`.
+    pub fn parse(text: &str, encoding_options: PetsciiEncodingOptions) -> Result<Self> {
+        Self::parse_with_dialect(text, KeywordTable::default(), encoding_options)
+    }
+
+    /// Tokenizes a plain-text listing using the keywords of `dialect`.
+    pub fn parse_with_dialect(
+        text: &str,
+        dialect: KeywordTable,
+        encoding_options: PetsciiEncodingOptions,
+    ) -> Result<Self> {
+        let contents = tokenize_listing(text, &dialect, &encoding_options)?;
+        Ok(Self::new(0x0801, encoding_options, contents).with_dialect(dialect))
+    }
+
+    /// Replaces the lines numbered within `line_numbers` with `lines`.
+    ///
+    /// Only `DATA` and `REM` lines can be replaced, and the new lines must be
+    /// in ascending order within the range. The line links are recalculated
+    /// by `to_bytes`.
+    pub fn replace_lines(&mut self, line_numbers: RangeInclusive<u16>, lines: Vec<BasicLine>) -> Result<()> {
+        if let Some(line) = self
+            .contents
+            .iter()
+            .find(|line| line_numbers.contains(&line.0) && line.is_code(&self.dialect))
+        {
+            return Err(Error::LineRangeOverlap(line.0));
+        }
+
+        let mut previous_line_number = None;
+        for BasicLine(line_number, _) in &lines {
+            if !line_numbers.contains(line_number) || previous_line_number.is_some_and(|n| n >= *line_number) {
+                return Err(Error::InvalidLineNumber(*line_number));
+            }
+            previous_line_number = Some(*line_number);
+        }
+
+        self.contents.retain(|line| !line_numbers.contains(&line.0));
+        let position = self.contents.partition_point(|line| line.0 < *line_numbers.start());
+        self.contents.splice(position..position, lines);
+        Ok(())
+    }
+
+    /// Number of bytes the program takes up in memory once loaded, including
+    /// the line links and the end-of-program marker.
+    pub fn size(&self) -> usize {
+        self.contents.iter().map(BasicLine::memory_size).sum::<usize>() + 2
+    }
+
+    /// First address past the program, where BASIC starts storing variables.
+    pub fn end_address(&self) -> usize {
+        self.load_address as usize + self.size()
+    }
+
+    /// Number of bytes left for variables, arrays and strings after loading
+    /// the program into `memory`, or `None` if the program doesn't fit.
+    pub fn free_bytes(&self, memory: &MemoryConfiguration) -> Option<usize> {
+        memory.available_from(self.load_address)?.checked_sub(self.size())
+    }
+
+    /// Lists the program as text the way `LIST` does, one line per numbered
+    /// line, using the keywords of its dialect. Characters without a Unicode
+    /// counterpart are written as `{clr}` or `{$a0}`.
+    pub fn to_listing(&self) -> String {
+        let mut listing = String::new();
+        for BasicLine(line_number, line_tokens) in &self.contents {
+            let line_bin = line_tokens
+                .iter()
+                .flat_map(|t| t.to_bytes(&self.encoding_options))
+                .collect::<Vec<u8>>();
+            listing.push_str(&format!(
+                "{} {}\n",
+                line_number,
+                list_line(&line_bin, &self.dialect, &self.encoding_options)
+            ));
+        }
+        listing
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BasicProgramWriter::new(vec![], self.load_address, self.encoding_options)
+            .expect("writing to a Vec can't fail");
+        for line in &self.contents {
+            writer.write_line(line).expect("writing to a Vec can't fail");
+        }
+        writer.finish().expect("writing to a Vec can't fail")
+    }
+}
+
+/// Writes a program line by line without holding it in memory, linking every
+/// line to the next one as it goes. Produces the same bytes as
+/// `BasicProgram::to_bytes`.
+pub struct BasicProgramWriter<W: Write> {
+    writer: W,
+    encoding_options: PetsciiEncodingOptions,
+    next_line_address: u16,
+}
+
+impl<W: Write> BasicProgramWriter<W> {
+    /// Starts the program by writing its load address.
+    pub fn new(mut writer: W, load_address: u16, encoding_options: PetsciiEncodingOptions) -> io::Result<Self> {
+        writer.write_all(&load_address.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            encoding_options,
+            next_line_address: load_address,
+        })
+    }
+
+    pub fn write_line(&mut self, BasicLine(line_number, line_tokens): &BasicLine) -> io::Result<()> {
+        let mut line_bin = vec![0x00, 0x00];
+        line_bin.extend(line_number.to_le_bytes());
+        line_bin.extend(line_tokens.iter().flat_map(|t| t.to_bytes(&self.encoding_options)));
+        line_bin.push(0x00);
+
+        self.next_line_address += line_bin.len() as u16;
+        line_bin[..2].copy_from_slice(&self.next_line_address.to_le_bytes());
+
+        self.writer.write_all(&line_bin)
+    }
+
+    /// Ends the program with a null link and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&0x0000u16.to_le_bytes())?;
+        Ok(self.writer)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::dialect::KeywordTable;
+    use crate::error::Error;
+    use crate::memory::MemoryConfiguration;
     use crate::petscii::{PetsciiEncodingOptions, PetsciiVariant};
-    use crate::program::{BasicLine, BasicProgram};
-    use crate::token::BasicKeyword;
+    use crate::program::{BasicLine, BasicProgram, BasicProgramWriter, MAX_LINE_LENGTH};
+    use crate::token::{BasicKeyword, BasicToken};
+
+    #[test]
+    pub fn test_push_token() {
+        let mut basic_line = BasicLine(10, vec![BasicKeyword::Data.into()]);
+
+        assert_eq!(basic_line.push_token(" 1,2,3".into(), 9), Ok(()));
+        assert_eq!(basic_line.push_token(",4".into(), 9), Ok(()));
+        assert_eq!(basic_line.push_token(",5".into(), 9), Err(Error::LineOverflow));
+        assert_eq!(
+            basic_line.push_token(",5".into(), MAX_LINE_LENGTH + 1),
+            Err(Error::InvalidLineLength(MAX_LINE_LENGTH + 1))
+        );
+        assert_eq!(basic_line.size(), 9);
+    }
 
     #[test]
     pub fn test_data() {
@@ -67,6 +306,7 @@ mod tests {
             encoding_options: PetsciiEncodingOptions {
                 variant: PetsciiVariant::Unshifted,
             },
+            dialect: KeywordTable::default(),
             #[rustfmt::skip]
             contents: vec![
                 BasicLine(10, vec![BasicKeyword::Data.into(), " 1,2,3,4".into()]),
@@ -98,6 +338,7 @@ mod tests {
             encoding_options: PetsciiEncodingOptions {
                 variant: PetsciiVariant::Unshifted,
             },
+            dialect: KeywordTable::default(),
             #[rustfmt::skip]
             contents: vec![
                 BasicLine(10, vec![BasicKeyword::Rem.into(), " 0123456789".into()]),
@@ -123,6 +364,7 @@ mod tests {
             encoding_options: PetsciiEncodingOptions {
                 variant: PetsciiVariant::Shifted,
             },
+            dialect: KeywordTable::default(),
             #[rustfmt::skip]
             contents: vec![
                 BasicLine(10, vec![BasicKeyword::Rem.into(), " \"0123456789\"".into()]),
@@ -144,4 +386,189 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    pub fn test_from_bytes() {
+        let basic_program = BasicProgram {
+            load_address: 0x0801,
+            encoding_options: PetsciiEncodingOptions::default(),
+            dialect: KeywordTable::default(),
+            #[rustfmt::skip]
+            contents: vec![
+                BasicLine(10, vec![BasicKeyword::Rem.into(), " LOADED".into()]),
+                BasicLine(20, vec![BasicKeyword::Data.into(), " 1,2".into()]),
+            ],
+        };
+        let prg_bin = basic_program.to_bytes();
+
+        let loaded_program = BasicProgram::from_bytes(&prg_bin, PetsciiEncodingOptions::default()).unwrap();
+        assert_eq!(loaded_program.load_address, 0x0801);
+        assert_eq!(loaded_program.contents.len(), 2);
+        assert_eq!(loaded_program.to_bytes(), prg_bin);
+
+        assert_eq!(
+            BasicProgram::from_bytes(&prg_bin[..prg_bin.len() - 2], PetsciiEncodingOptions::default()).unwrap_err(),
+            Error::MalformedProgram
+        );
+        assert_eq!(
+            BasicProgram::from_bytes(&[0x01], PetsciiEncodingOptions::default()).unwrap_err(),
+            Error::MalformedProgram
+        );
+
+        let mut broken_bin = prg_bin.clone();
+        broken_bin[2] += 1;
+        assert_eq!(
+            BasicProgram::from_bytes(&broken_bin, PetsciiEncodingOptions::default()).unwrap_err(),
+            Error::InvalidLinkPointer(10)
+        );
+    }
+
+    #[test]
+    pub fn test_to_listing() {
+        let listing = "10 PRINT \"HELLO\";:GOTO 10\n20 DATA 1,2:REM END\n";
+        let basic_program = BasicProgram::parse(listing, PetsciiEncodingOptions::default()).unwrap();
+        assert_eq!(basic_program.to_listing(), listing);
+
+        let loaded_program =
+            BasicProgram::from_bytes(&basic_program.to_bytes(), PetsciiEncodingOptions::default()).unwrap();
+        assert_eq!(loaded_program.to_listing(), listing);
+
+        let listing = "10 FAST:GRAPHIC 1\n";
+        let basic_program =
+            BasicProgram::parse_with_dialect(listing, KeywordTable::basic_70(), PetsciiEncodingOptions::default())
+                .unwrap();
+        assert_eq!(basic_program.to_listing(), listing);
+
+        let loaded_program =
+            BasicProgram::from_bytes(&basic_program.to_bytes(), PetsciiEncodingOptions::default()).unwrap();
+        assert_eq!(loaded_program.to_listing(), "10 {$fe}%:{$de} 1\n");
+        let loaded_program = loaded_program.with_dialect(KeywordTable::basic_70());
+        assert_eq!(loaded_program.to_listing(), listing);
+
+        let basic_program = BasicProgram {
+            load_address: 0x0801,
+            encoding_options: PetsciiEncodingOptions::default(),
+            dialect: KeywordTable::default(),
+            contents: vec![BasicLine(
+                10,
+                vec![BasicToken::Bytes(b"\x99 \"\x93\x1c\xa0\"".to_vec())],
+            )],
+        };
+        assert_eq!(basic_program.to_listing(), "10 PRINT \"{clr}{red}{$a0}\"\n");
+    }
+
+    #[test]
+    pub fn test_replace_lines() {
+        let mut basic_program = BasicProgram {
+            load_address: 0x0801,
+            encoding_options: PetsciiEncodingOptions::default(),
+            dialect: KeywordTable::default(),
+            #[rustfmt::skip]
+            contents: vec![
+                BasicLine(10, vec![BasicKeyword::Read.into(), " A".into()]),
+                BasicLine(20, vec![BasicKeyword::End.into()]),
+                BasicLine(100, vec![BasicToken::Bytes(vec![0x83, 0x20, 0x31])]),
+                BasicLine(110, vec![BasicToken::Bytes(vec![0x20, 0x8F, 0x20, 0x41])]),
+                BasicLine(200, vec![BasicKeyword::Rem.into(), " END".into()]),
+            ],
+        };
+
+        assert_eq!(
+            basic_program.replace_lines(20..=199, vec![]),
+            Err(Error::LineRangeOverlap(20))
+        );
+        assert_eq!(
+            basic_program.replace_lines(100..=199, vec![BasicLine(200, vec![])]),
+            Err(Error::InvalidLineNumber(200))
+        );
+        assert_eq!(
+            basic_program.replace_lines(100..=199, vec![BasicLine(120, vec![]), BasicLine(110, vec![])]),
+            Err(Error::InvalidLineNumber(110))
+        );
+
+        #[rustfmt::skip]
+        let data_lines = vec![
+            BasicLine(100, vec![BasicKeyword::Data.into(), " 2".into()]),
+            BasicLine(101, vec![BasicKeyword::Data.into(), " 3".into()]),
+            BasicLine(102, vec![BasicKeyword::Data.into(), " 4".into()]),
+        ];
+        assert_eq!(basic_program.replace_lines(100..=199, data_lines), Ok(()));
+        assert_eq!(
+            basic_program.contents.iter().map(|line| line.0).collect::<Vec<_>>(),
+            vec![10, 20, 100, 101, 102, 200]
+        );
+
+        #[rustfmt::skip]
+        basic_program.contents.insert(5, BasicLine(150, vec![
+            BasicKeyword::Data.into(), " \"A:B\", 1:".into(), BasicKeyword::Goto.into(), " 10".into(),
+        ]));
+        assert_eq!(
+            basic_program.replace_lines(100..=199, vec![]),
+            Err(Error::LineRangeOverlap(150))
+        );
+
+        #[rustfmt::skip]
+        let remark_line = BasicLine(150, vec![
+            BasicKeyword::Data.into(), " \"A:B\": ".into(), BasicKeyword::Rem.into(), " GOTO 10".into(),
+        ]);
+        basic_program.contents[5] = remark_line;
+        assert_eq!(basic_program.replace_lines(100..=199, vec![]), Ok(()));
+        assert_eq!(
+            basic_program.contents.iter().map(|line| line.0).collect::<Vec<_>>(),
+            vec![10, 20, 200]
+        );
+    }
+
+    #[test]
+    pub fn test_program_writer() {
+        let basic_program = BasicProgram {
+            load_address: 0xC000,
+            encoding_options: PetsciiEncodingOptions::default(),
+            dialect: KeywordTable::default(),
+            contents: vec![
+                BasicLine(10, vec![BasicKeyword::Rem.into(), " HELLO".into()]),
+                BasicLine(20, vec![BasicKeyword::Data.into(), " 1,2,3".into()]),
+            ],
+        };
+
+        let mut writer = BasicProgramWriter::new(vec![], 0xC000, PetsciiEncodingOptions::default()).unwrap();
+        for line in &basic_program.contents {
+            writer.write_line(line).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), basic_program.to_bytes());
+
+        let writer = BasicProgramWriter::new(vec![], 0x0801, PetsciiEncodingOptions::default()).unwrap();
+        assert_eq!(writer.finish().unwrap(), vec![0x01, 0x08, 0x00, 0x00]);
+    }
+
+    #[test]
+    pub fn test_program_size() {
+        let mut basic_program = BasicProgram {
+            load_address: 0x0801,
+            encoding_options: PetsciiEncodingOptions::default(),
+            dialect: KeywordTable::default(),
+            contents: vec![
+                BasicLine(10, vec![BasicKeyword::Rem.into(), " HELLO".into()]),
+                BasicLine(20, vec![BasicKeyword::Data.into(), " 1,2,3".into()]),
+            ],
+        };
+
+        assert_eq!(basic_program.size(), basic_program.to_bytes().len() - 2);
+        assert_eq!(basic_program.end_address(), 0x0801 + 26);
+        assert_eq!(basic_program.free_bytes(&MemoryConfiguration::C64), Some(38911 - 26));
+
+        let memory = MemoryConfiguration {
+            start: 0x0801,
+            end: 0x0801 + 26,
+        };
+        assert_eq!(basic_program.free_bytes(&memory), Some(0));
+
+        basic_program
+            .contents
+            .push(BasicLine(30, vec![BasicKeyword::Rem.into()]));
+        assert_eq!(basic_program.free_bytes(&memory), None);
+
+        basic_program.load_address = 0x0400;
+        assert_eq!(basic_program.free_bytes(&MemoryConfiguration::C64), None);
+    }
 }
@@ -4,6 +4,9 @@ use crate::petscii::{PetsciiEncodingOptions, PetsciiString};
 pub enum BasicToken {
     Keyword(BasicKeyword),
     Raw(PetsciiString),
+    /// Already tokenized and encoded contents, e.g. a keyword of a dialect or
+    /// a line of a loaded program.
+    Bytes(Vec<u8>),
 }
 
 #[allow(unused)]
@@ -32,11 +35,132 @@ pub enum BasicKeyword {
     Left      = 0xC8, Right     = 0xC9, Mid       = 0xCA, Go        = 0xCB,
 }
 
+/// Single-byte keywords BASIC 3.5 adds after those of BASIC V2. BASIC 7.0 has
+/// all of them except `RLUM`, as it uses `$CE` as a prefix.
+#[allow(unused)]
+#[rustfmt::skip]
+#[derive(Debug, Copy, Clone)]
+#[repr(u8)]
+pub enum Basic35Keyword {
+    Rgr       = 0xCC, Rclr      = 0xCD, Rlum      = 0xCE, Joy       = 0xCF,
+    Rdot      = 0xD0, Dec       = 0xD1, Hex       = 0xD2, Err       = 0xD3,
+    Instr     = 0xD4, Else      = 0xD5, Resume    = 0xD6, Trap      = 0xD7,
+    Tron      = 0xD8, Troff     = 0xD9, Sound     = 0xDA, Vol       = 0xDB,
+    Auto      = 0xDC, Pudef     = 0xDD, Graphic   = 0xDE, Paint     = 0xDF,
+    Char      = 0xE0, Box       = 0xE1, Circle    = 0xE2, Gshape    = 0xE3,
+    Sshape    = 0xE4, Draw      = 0xE5, Locate    = 0xE6, Color     = 0xE7,
+    Scnclr    = 0xE8, Scale     = 0xE9, Help      = 0xEA, Do        = 0xEB,
+    Loop      = 0xEC, Exit      = 0xED, Directory = 0xEE, Dsave     = 0xEF,
+    Dload     = 0xF0, Header    = 0xF1, Scratch   = 0xF2, Collect   = 0xF3,
+    Copy      = 0xF4, Rename    = 0xF5, Backup    = 0xF6, Delete    = 0xF7,
+    Renumber  = 0xF8, Key       = 0xF9, Monitor   = 0xFA, Using     = 0xFB,
+    Until     = 0xFC, While     = 0xFD,
+}
+
+/// Two-byte keywords of BASIC 7.0, functions prefixed with `$CE` and
+/// statements prefixed with `$FE`.
+#[allow(unused)]
+#[rustfmt::skip]
+#[derive(Debug, Copy, Clone)]
+#[repr(u16)]
+pub enum Basic70Keyword {
+    Pot       = 0xCE02, Bump      = 0xCE03, Pen       = 0xCE04, Rsppos    = 0xCE05,
+    Rsprite   = 0xCE06, Rspcolor  = 0xCE07, Xor       = 0xCE08, Rwindow   = 0xCE09,
+    Pointer   = 0xCE0A,
+
+    Bank      = 0xFE02, Filter    = 0xFE03, Play      = 0xFE04, Tempo     = 0xFE05,
+    Movspr    = 0xFE06, Sprite    = 0xFE07, Sprcolor  = 0xFE08, Rreg      = 0xFE09,
+    Envelope  = 0xFE0A, Sleep     = 0xFE0B, Catalog   = 0xFE0C, Dopen     = 0xFE0D,
+    Append    = 0xFE0E, Dclose    = 0xFE0F, Bsave     = 0xFE10, Bload     = 0xFE11,
+    Record    = 0xFE12, Concat    = 0xFE13, Dverify   = 0xFE14, Dclear    = 0xFE15,
+    Sprsav    = 0xFE16, Collision = 0xFE17, Begin     = 0xFE18, Bend      = 0xFE19,
+    Window    = 0xFE1A, Boot      = 0xFE1B, Width     = 0xFE1C, Sprdef    = 0xFE1D,
+    Quit      = 0xFE1E, Stash     = 0xFE1F, Fetch     = 0xFE21, Swap      = 0xFE23,
+    Off       = 0xFE24, Fast      = 0xFE25, Slow      = 0xFE26,
+}
+
+/// Token of `π`, the only keyword without a name in the keyword tables.
+pub(crate) const PI_TOKEN: u8 = 0xFF;
+
+/// Keywords of BASIC V2 by the names `LIST` prints them with.
+#[rustfmt::skip]
+pub const BASIC_V2_KEYWORDS: &[(&str, BasicKeyword)] = &[
+    ("END", BasicKeyword::End), ("FOR", BasicKeyword::For), ("NEXT", BasicKeyword::Next),
+    ("DATA", BasicKeyword::Data), ("INPUT#", BasicKeyword::InputHash), ("INPUT", BasicKeyword::Input),
+    ("DIM", BasicKeyword::Dim), ("READ", BasicKeyword::Read), ("LET", BasicKeyword::Let),
+    ("GOTO", BasicKeyword::Goto), ("RUN", BasicKeyword::Run), ("IF", BasicKeyword::If),
+    ("RESTORE", BasicKeyword::Restore), ("GOSUB", BasicKeyword::Gosub), ("RETURN", BasicKeyword::Return),
+    ("REM", BasicKeyword::Rem), ("STOP", BasicKeyword::Stop), ("ON", BasicKeyword::On),
+    ("WAIT", BasicKeyword::Wait), ("LOAD", BasicKeyword::Load), ("SAVE", BasicKeyword::Save),
+    ("VERIFY", BasicKeyword::Verify), ("DEF", BasicKeyword::Def), ("POKE", BasicKeyword::Poke),
+    ("PRINT#", BasicKeyword::PrintHash), ("PRINT", BasicKeyword::Print), ("CONT", BasicKeyword::Cont),
+    ("LIST", BasicKeyword::List), ("CLR", BasicKeyword::Clr), ("CMD", BasicKeyword::Cmd),
+    ("SYS", BasicKeyword::Sys), ("OPEN", BasicKeyword::Open), ("CLOSE", BasicKeyword::Close),
+    ("GET", BasicKeyword::Get), ("NEW", BasicKeyword::New), ("TAB(", BasicKeyword::Tab),
+    ("TO", BasicKeyword::To), ("FN", BasicKeyword::Fn), ("SPC(", BasicKeyword::Spc),
+    ("THEN", BasicKeyword::Then), ("NOT", BasicKeyword::Not), ("STEP", BasicKeyword::Step),
+    ("+", BasicKeyword::OpAdd), ("-", BasicKeyword::OpSub), ("*", BasicKeyword::OpMul),
+    ("/", BasicKeyword::OpDiv), ("^", BasicKeyword::OpPow), ("AND", BasicKeyword::And),
+    ("OR", BasicKeyword::Or), (">", BasicKeyword::OpGreater), ("=", BasicKeyword::OpEquals),
+    ("<", BasicKeyword::OpLess), ("SGN", BasicKeyword::Sgn), ("INT", BasicKeyword::Int),
+    ("ABS", BasicKeyword::Abs), ("USR", BasicKeyword::Usr), ("FRE", BasicKeyword::Fre),
+    ("POS", BasicKeyword::Pos), ("SQR", BasicKeyword::Sqr), ("RND", BasicKeyword::Rnd),
+    ("LOG", BasicKeyword::Log), ("EXP", BasicKeyword::Exp), ("COS", BasicKeyword::Cos),
+    ("SIN", BasicKeyword::Sin), ("TAN", BasicKeyword::Tan), ("ATN", BasicKeyword::Atn),
+    ("PEEK", BasicKeyword::Peek), ("LEN", BasicKeyword::Len), ("STR$", BasicKeyword::Str),
+    ("VAL", BasicKeyword::Val), ("ASC", BasicKeyword::Asc), ("CHR$", BasicKeyword::Chr),
+    ("LEFT$", BasicKeyword::Left), ("RIGHT$", BasicKeyword::Right), ("MID$", BasicKeyword::Mid),
+    ("GO", BasicKeyword::Go),
+];
+
+/// Keywords BASIC 3.5 adds to those of BASIC V2.
+#[rustfmt::skip]
+pub const BASIC_35_KEYWORDS: &[(&str, Basic35Keyword)] = &[
+    ("RGR", Basic35Keyword::Rgr), ("RCLR", Basic35Keyword::Rclr), ("RLUM", Basic35Keyword::Rlum),
+    ("JOY", Basic35Keyword::Joy), ("RDOT", Basic35Keyword::Rdot), ("DEC", Basic35Keyword::Dec),
+    ("HEX$", Basic35Keyword::Hex), ("ERR$", Basic35Keyword::Err), ("INSTR", Basic35Keyword::Instr),
+    ("ELSE", Basic35Keyword::Else), ("RESUME", Basic35Keyword::Resume), ("TRAP", Basic35Keyword::Trap),
+    ("TRON", Basic35Keyword::Tron), ("TROFF", Basic35Keyword::Troff), ("SOUND", Basic35Keyword::Sound),
+    ("VOL", Basic35Keyword::Vol), ("AUTO", Basic35Keyword::Auto), ("PUDEF", Basic35Keyword::Pudef),
+    ("GRAPHIC", Basic35Keyword::Graphic), ("PAINT", Basic35Keyword::Paint), ("CHAR", Basic35Keyword::Char),
+    ("BOX", Basic35Keyword::Box), ("CIRCLE", Basic35Keyword::Circle), ("GSHAPE", Basic35Keyword::Gshape),
+    ("SSHAPE", Basic35Keyword::Sshape), ("DRAW", Basic35Keyword::Draw), ("LOCATE", Basic35Keyword::Locate),
+    ("COLOR", Basic35Keyword::Color), ("SCNCLR", Basic35Keyword::Scnclr), ("SCALE", Basic35Keyword::Scale),
+    ("HELP", Basic35Keyword::Help), ("DO", Basic35Keyword::Do), ("LOOP", Basic35Keyword::Loop),
+    ("EXIT", Basic35Keyword::Exit), ("DIRECTORY", Basic35Keyword::Directory), ("DSAVE", Basic35Keyword::Dsave),
+    ("DLOAD", Basic35Keyword::Dload), ("HEADER", Basic35Keyword::Header), ("SCRATCH", Basic35Keyword::Scratch),
+    ("COLLECT", Basic35Keyword::Collect), ("COPY", Basic35Keyword::Copy), ("RENAME", Basic35Keyword::Rename),
+    ("BACKUP", Basic35Keyword::Backup), ("DELETE", Basic35Keyword::Delete), ("RENUMBER", Basic35Keyword::Renumber),
+    ("KEY", Basic35Keyword::Key), ("MONITOR", Basic35Keyword::Monitor), ("USING", Basic35Keyword::Using),
+    ("UNTIL", Basic35Keyword::Until), ("WHILE", Basic35Keyword::While),
+];
+
+/// Two-byte keywords BASIC 7.0 adds to those of BASIC 3.5.
+#[rustfmt::skip]
+pub const BASIC_70_KEYWORDS: &[(&str, Basic70Keyword)] = &[
+    ("POT", Basic70Keyword::Pot), ("BUMP", Basic70Keyword::Bump), ("PEN", Basic70Keyword::Pen),
+    ("RSPPOS", Basic70Keyword::Rsppos), ("RSPRITE", Basic70Keyword::Rsprite), ("RSPCOLOR", Basic70Keyword::Rspcolor),
+    ("XOR", Basic70Keyword::Xor), ("RWINDOW", Basic70Keyword::Rwindow), ("POINTER", Basic70Keyword::Pointer),
+    ("BANK", Basic70Keyword::Bank), ("FILTER", Basic70Keyword::Filter), ("PLAY", Basic70Keyword::Play),
+    ("TEMPO", Basic70Keyword::Tempo), ("MOVSPR", Basic70Keyword::Movspr), ("SPRITE", Basic70Keyword::Sprite),
+    ("SPRCOLOR", Basic70Keyword::Sprcolor), ("RREG", Basic70Keyword::Rreg), ("ENVELOPE", Basic70Keyword::Envelope),
+    ("SLEEP", Basic70Keyword::Sleep), ("CATALOG", Basic70Keyword::Catalog), ("DOPEN", Basic70Keyword::Dopen),
+    ("APPEND", Basic70Keyword::Append), ("DCLOSE", Basic70Keyword::Dclose), ("BSAVE", Basic70Keyword::Bsave),
+    ("BLOAD", Basic70Keyword::Bload), ("RECORD", Basic70Keyword::Record), ("CONCAT", Basic70Keyword::Concat),
+    ("DVERIFY", Basic70Keyword::Dverify), ("DCLEAR", Basic70Keyword::Dclear), ("SPRSAV", Basic70Keyword::Sprsav),
+    ("COLLISION", Basic70Keyword::Collision), ("BEGIN", Basic70Keyword::Begin), ("BEND", Basic70Keyword::Bend),
+    ("WINDOW", Basic70Keyword::Window), ("BOOT", Basic70Keyword::Boot), ("WIDTH", Basic70Keyword::Width),
+    ("SPRDEF", Basic70Keyword::Sprdef), ("QUIT", Basic70Keyword::Quit), ("STASH", Basic70Keyword::Stash),
+    ("FETCH", Basic70Keyword::Fetch), ("SWAP", Basic70Keyword::Swap), ("OFF", Basic70Keyword::Off),
+    ("FAST", Basic70Keyword::Fast), ("SLOW", Basic70Keyword::Slow),
+];
+
 impl BasicToken {
     pub fn to_bytes(&self, encoding_options: &PetsciiEncodingOptions) -> Vec<u8> {
         match self {
             BasicToken::Keyword(keyword) => vec![*keyword as u8],
             BasicToken::Raw(string) => string.to_petscii(encoding_options),
+            BasicToken::Bytes(bytes) => bytes.clone(),
         }
     }
 
@@ -44,6 +168,7 @@ impl BasicToken {
         match self {
             BasicToken::Keyword(_) => 1,
             BasicToken::Raw(PetsciiString(string)) => string.chars().count(),
+            BasicToken::Bytes(bytes) => bytes.len(),
         }
     }
 }
@@ -59,3 +184,82 @@ impl From<BasicKeyword> for BasicToken {
         BasicToken::Keyword(keyword)
     }
 }
+
+impl From<Basic35Keyword> for BasicToken {
+    fn from(keyword: Basic35Keyword) -> Self {
+        BasicToken::Bytes(vec![keyword as u8])
+    }
+}
+
+impl From<Basic70Keyword> for BasicToken {
+    fn from(keyword: Basic70Keyword) -> Self {
+        BasicToken::Bytes((keyword as u16).to_be_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::petscii::PetsciiEncodingOptions;
+    use crate::program::BasicLine;
+    use crate::token::{
+        Basic35Keyword, Basic70Keyword, BasicKeyword, BasicToken, BASIC_35_KEYWORDS, BASIC_70_KEYWORDS,
+        BASIC_V2_KEYWORDS,
+    };
+
+    #[test]
+    pub fn test_keyword_bytes() {
+        let line = BasicLine(
+            10,
+            vec![
+                Basic35Keyword::Do.into(),
+                ":".into(),
+                Basic70Keyword::Fast.into(),
+                ":".into(),
+                Basic35Keyword::Loop.into(),
+                " ".into(),
+                Basic35Keyword::Until.into(),
+                " ".into(),
+                Basic70Keyword::Pot.into(),
+                "(1)".into(),
+                BasicKeyword::OpEquals.into(),
+                "0".into(),
+            ],
+        );
+
+        let line_bin = line
+            .1
+            .iter()
+            .flat_map(|t| t.to_bytes(&PetsciiEncodingOptions::default()))
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            line_bin,
+            vec![0xEB, 0x3A, 0xFE, 0x25, 0x3A, 0xEC, 0x20, 0xFC, 0x20, 0xCE, 0x02, 0x28, 0x31, 0x29, 0xB2, 0x30]
+        );
+        assert_eq!(line.size(), line_bin.len());
+    }
+
+    #[test]
+    pub fn test_keyword_tables() {
+        let mut names = HashSet::new();
+        let mut codes = HashSet::new();
+
+        for (name, keyword) in BASIC_V2_KEYWORDS {
+            assert!(names.insert(*name));
+            assert!(codes.insert(BasicToken::from(*keyword).to_bytes(&PetsciiEncodingOptions::default())));
+        }
+        for (name, keyword) in BASIC_35_KEYWORDS {
+            assert!(names.insert(*name));
+            assert!(codes.insert(BasicToken::from(*keyword).to_bytes(&PetsciiEncodingOptions::default())));
+        }
+        for (name, keyword) in BASIC_70_KEYWORDS {
+            assert!(names.insert(*name));
+            assert!(codes.insert(BasicToken::from(*keyword).to_bytes(&PetsciiEncodingOptions::default())));
+        }
+
+        assert_eq!(BASIC_V2_KEYWORDS.len(), 0xCB - 0x80 + 1);
+        assert_eq!(BASIC_35_KEYWORDS.len(), 0xFD - 0xCC + 1);
+        assert_eq!(BASIC_70_KEYWORDS.len(), 9 + 35);
+    }
+}
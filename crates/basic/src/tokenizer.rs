@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+
+use crate::dialect::BasicDialect;
+use crate::error::{Error, Result};
+use crate::petscii::{PetsciiEncodingOptions, PetsciiString};
+use crate::program::{BasicLine, MAX_LINE_LENGTH, MAX_LINE_NUMBER};
+use crate::token::{BasicToken, PI_TOKEN};
+
+const PETSCII_QUESTION_MARK: u8 = 0x3F;
+
+/// Tokenizes a plain-text listing as if its lines were typed into the screen
+/// editor: the lines are sorted by line number, a repeated line number
+/// replaces the earlier line and a line number alone deletes the line.
+pub fn tokenize_listing(
+    text: &str,
+    dialect: &dyn BasicDialect,
+    encoding_options: &PetsciiEncodingOptions,
+) -> Result<Vec<BasicLine>> {
+    let mut lines = BTreeMap::new();
+
+    for (index, text_line) in text.lines().enumerate() {
+        let chars = text_line.chars().collect::<Vec<char>>();
+        let error = |column: usize, reason| Error::ParseError {
+            line: index + 1,
+            column: column + 1,
+            reason,
+        };
+
+        let mut pos = chars.iter().position(|c| *c != ' ').unwrap_or(chars.len());
+        if pos == chars.len() {
+            continue;
+        }
+
+        let line_number_start = pos;
+        let mut line_number = 0u32;
+        while let Some(digit) = chars.get(pos).and_then(|c| c.to_digit(10)) {
+            line_number = line_number * 10 + digit;
+            if line_number > MAX_LINE_NUMBER as u32 {
+                return Err(error(line_number_start, "line number is larger than MAX_LINE_NUMBER"));
+            }
+            pos += 1;
+        }
+        if pos == line_number_start {
+            return Err(error(pos, "missing line number"));
+        }
+
+        while chars.get(pos) == Some(&' ') {
+            pos += 1;
+        }
+
+        let line_number = line_number as u16;
+        if pos == chars.len() {
+            lines.remove(&line_number);
+            continue;
+        }
+
+        let tokens = tokenize_statements(&chars[pos..], dialect, encoding_options)
+            .map_err(|(column, reason)| error(pos + column, reason))?;
+
+        let mut line = BasicLine(line_number, vec![]);
+        for (column, token) in tokens {
+            line.push_token(token, MAX_LINE_LENGTH)
+                .map_err(|_| error(pos + column, "line is longer than MAX_LINE_LENGTH"))?;
+        }
+        lines.insert(line_number, line);
+    }
+
+    Ok(lines.into_values().collect())
+}
+
+/// Tokenizes the text following the line number, returning the tokens with
+/// the columns they start at, or the column and reason of the error.
+///
+/// Like the C64 ROM, it replaces keywords wherever they appear outside quotes,
+/// even inside variable names (`TOTAL` becomes `TO` and `TAL`), but leaves
+/// `DATA` items up to the next `:` and everything after `REM` alone. `?` is
+/// short for `PRINT`.
+fn tokenize_statements(
+    chars: &[char],
+    dialect: &dyn BasicDialect,
+    encoding_options: &PetsciiEncodingOptions,
+) -> std::result::Result<Vec<(usize, BasicToken)>, (usize, &'static str)> {
+    // Keywords are matched against the PETSCII codes of the text, so both
+    // character sets match them in the case they are typed in.
+    let mut petscii = vec![];
+    for (column, c) in chars.iter().enumerate() {
+        let b = PetsciiString(c.to_string()).to_petscii(encoding_options)[0];
+        if b == PETSCII_QUESTION_MARK && *c != '?' {
+            return Err((column, "character can't be encoded in PETSCII"));
+        }
+        petscii.push(b as char);
+    }
+
+    let mut tokens = vec![];
+    let mut raw = String::new();
+    let mut raw_start = 0;
+    let mut quoted = false;
+    let mut in_data = false;
+    let mut pos = 0;
+
+    let flush = |raw: &mut String, raw_start: usize, tokens: &mut Vec<(usize, BasicToken)>| {
+        if !raw.is_empty() {
+            tokens.push((raw_start, BasicToken::from(raw.as_str())));
+            raw.clear();
+        }
+    };
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if raw.is_empty() {
+            raw_start = pos;
+        }
+
+        if c == '"' {
+            quoted = !quoted;
+        }
+
+        if quoted || c == '"' || in_data {
+            in_data &= quoted || c != ':';
+            raw.push(c);
+            pos += 1;
+            continue;
+        }
+
+        let keyword = match c {
+            'π' => Some((1, vec![PI_TOKEN])),
+            '?' => dialect.keyword_bytes("PRINT").map(|bytes| (1, bytes.to_vec())),
+            ' ' | '0'..='9' | ':' | ';' => None,
+            _ => {
+                let rest = petscii[pos..].iter().collect::<String>();
+                dialect
+                    .match_keyword(&rest)
+                    .map(|(name, bytes)| (name.chars().count(), bytes.to_vec()))
+            }
+        };
+
+        match keyword {
+            Some((length, bytes)) => {
+                flush(&mut raw, raw_start, &mut tokens);
+                tokens.push((pos, BasicToken::Bytes(bytes.clone())));
+                pos += length;
+
+                if dialect.keyword_bytes("REM") == Some(&bytes) {
+                    if pos < chars.len() {
+                        tokens.push((pos, BasicToken::from(chars[pos..].iter().collect::<String>().as_str())));
+                    }
+                    return Ok(tokens);
+                }
+                in_data = dialect.keyword_bytes("DATA") == Some(&bytes);
+            }
+            None => {
+                raw.push(c);
+                pos += 1;
+            }
+        }
+    }
+
+    flush(&mut raw, raw_start, &mut tokens);
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::KeywordTable;
+    use crate::error::Error;
+    use crate::petscii::{PetsciiEncodingOptions, PetsciiVariant};
+    use crate::program::BasicLine;
+    use crate::tokenizer::tokenize_listing;
+
+    fn tokenize(text: &str, encoding_options: &PetsciiEncodingOptions) -> Vec<(u16, Vec<u8>)> {
+        tokenize_with_dialect(text, &KeywordTable::default(), encoding_options)
+    }
+
+    fn tokenize_with_dialect(
+        text: &str,
+        dialect: &KeywordTable,
+        encoding_options: &PetsciiEncodingOptions,
+    ) -> Vec<(u16, Vec<u8>)> {
+        tokenize_listing(text, dialect, encoding_options)
+            .unwrap()
+            .iter()
+            .map(|BasicLine(line_number, tokens)| {
+                let line_bin = tokens.iter().flat_map(|t| t.to_bytes(encoding_options)).collect();
+                (*line_number, line_bin)
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_tokenize_listing() {
+        let unshifted = PetsciiEncodingOptions::default();
+
+        assert_eq!(
+            tokenize("10 PRINT \"HI\":GOTO 10", &unshifted),
+            vec![(10, b"\x99 \"HI\":\x89 10".to_vec())]
+        );
+        assert_eq!(
+            tokenize("10 PRINT \"GOTO\"; TOTAL", &unshifted),
+            vec![(10, b"\x99 \"GOTO\"; \xa4TAL".to_vec())]
+        );
+        assert_eq!(
+            tokenize("10 DATA PRINT, \"A:B\", 1:READ A$: REM GOTO", &unshifted),
+            vec![(10, b"\x83 PRINT, \"A:B\", 1:\x87 A$: \x8f GOTO".to_vec())]
+        );
+        assert_eq!(
+            tokenize("10 GO TO 20:GOTO 20:?π*2", &unshifted),
+            vec![(10, b"\xcb \xa4 20:\x89 20:\x99\xff\xac2".to_vec())]
+        );
+        assert_eq!(
+            tokenize("10 input#1,a$:print#1", &unshifted),
+            vec![(10, b"\x84\x31,A$:\x98\x31".to_vec())]
+        );
+
+        let shifted = PetsciiEncodingOptions {
+            variant: PetsciiVariant::Shifted,
+        };
+        assert_eq!(
+            tokenize("10 print \"Hi\";PRINT", &shifted),
+            vec![(10, b"\x99 \"\xc8I\";\xd0\xd2\xc9\xce\xd4".to_vec())]
+        );
+
+        assert_eq!(
+            tokenize("\n  20 END\n10 REM\n20\n 10   STOP\n", &unshifted),
+            vec![(10, b"\x90".to_vec())]
+        );
+    }
+
+    #[test]
+    pub fn test_tokenize_basic_70() {
+        let basic_70 = KeywordTable::basic_70();
+        let unshifted = PetsciiEncodingOptions::default();
+
+        assert_eq!(
+            tokenize_with_dialect("10 DOPEN#1,\"X\":DO:LOOP", &basic_70, &unshifted),
+            vec![(10, b"\xfe\x0d#1,\"X\":\xeb:\xec".to_vec())]
+        );
+        assert_eq!(
+            tokenize_with_dialect("10 GETKEY A$:GO64", &basic_70, &unshifted),
+            vec![(10, b"\xa1\xf9 A$:\xcb64".to_vec())]
+        );
+    }
+
+    #[test]
+    pub fn test_parse_errors() {
+        let error = |text: &str| tokenize_listing(text, &KeywordTable::default(), &PetsciiEncodingOptions::default());
+
+        assert_eq!(
+            error("10 END\nPRINT").err(),
+            Some(Error::ParseError {
+                line: 2,
+                column: 1,
+                reason: "missing line number"
+            })
+        );
+        assert!(matches!(
+            error("10 END\n\n 64000 END"),
+            Err(Error::ParseError { line: 3, column: 2, .. })
+        ));
+        assert!(matches!(
+            error("10 PRINT \"€\""),
+            Err(Error::ParseError {
+                line: 1,
+                column: 11,
+                ..
+            })
+        ));
+        assert!(matches!(
+            error(&format!("10 REM {}", "X".repeat(250))),
+            Err(Error::ParseError { line: 1, column: 7, .. })
+        ));
+    }
+}
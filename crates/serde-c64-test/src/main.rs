@@ -2,9 +2,13 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::time::Duration;
 
-use basic::PetsciiEncodingOptions;
+use basic::{Machine, PetsciiEncodingOptions};
 use serde::Serialize;
-use serde_c64::{ContainerPrefixOptions, SpacingOptions, StringQuotationMethod};
+use serde_c64::{
+    AnnotationMethod, ByteEncodingMethod, ChecksumMethod, ContainerPrefixOptions, ContainerTerminationMethod,
+    FloatOverflowMethod, LargeIntegerMethod, LineBreakMethod, LineNumberOverflowMethod, MemoryBudgetMethod,
+    SpacingOptions, StringQuotationMethod, StringSplitMethod,
+};
 
 #[derive(Serialize)]
 struct Person {
@@ -106,9 +110,13 @@ fn main() {
     let test_output = File::create("disk/test-output").unwrap();
 
     let options = serde_c64::Options {
+        machine: Machine::C64,
         line_length: 64,
         line_number_start: 1000,
         line_number_increment: 1,
+        line_number_limit: basic::MAX_LINE_NUMBER,
+        line_number_overflow: LineNumberOverflowMethod::Fail,
+        memory_budget_method: MemoryBudgetMethod::Unchecked,
         encoding_options: PetsciiEncodingOptions {
             variant: basic::PetsciiVariant::Shifted,
         },
@@ -117,12 +125,20 @@ fn main() {
             map_length: true,
             tuple_length: false,
         },
+        container_termination_method: ContainerTerminationMethod::LengthPrefix,
         spacing_options: SpacingOptions {
             space_after_data_statement: true,
             space_between_data_entries: true,
         },
+        line_break_method: LineBreakMethod::Record,
         string_quotation_method: StringQuotationMethod::WhenNecessary,
+        string_split_method: StringSplitMethod::Disabled,
+        byte_encoding_method: ByteEncodingMethod::Decimal,
+        float_overflow_method: FloatOverflowMethod::Fail,
+        large_integer_method: LargeIntegerMethod::Fail,
         emit_enum_names: true,
+        annotation_method: AnnotationMethod::Disabled,
+        checksum_method: ChecksumMethod::Disabled,
     };
 
     serde_c64::to_writer(test_output, &test_data, options).unwrap();
@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::Serialize;
+use serde_c64::{to_vec, to_writer, Options};
+
+#[derive(Serialize)]
+struct Person {
+    name: String,
+    birth_year: u16,
+    height: f32,
+    tags: Vec<String>,
+}
+
+fn people(count: usize) -> Vec<Person> {
+    (0..count)
+        .map(|i| Person {
+            name: format!("PERSON {}", i),
+            birth_year: 1900 + (i % 100) as u16,
+            height: 150.0 + (i % 50) as f32 / 2.0,
+            tags: vec![String::from("C64"), format!("TAG {}", i % 7)],
+        })
+        .collect()
+}
+
+/// Compares building the whole program in memory before writing it with
+/// streaming the lines straight to the writer.
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+
+    for count in [100, 1000, 5000] {
+        let data = people(count);
+        let prg_size = to_vec(&data, Options::default()).unwrap().len();
+        group.throughput(Throughput::Bytes(prg_size as u64));
+
+        group.bench_with_input(BenchmarkId::new("buffered", count), &data, |b, data| {
+            b.iter(|| {
+                let prg_bin = to_vec(data, Options::default()).unwrap();
+                io::sink().write_all(&prg_bin).unwrap();
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("streaming", count), &data, |b, data| {
+            b.iter(|| to_writer(io::sink(), data, Options::default()).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
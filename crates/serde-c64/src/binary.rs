@@ -0,0 +1,568 @@
+use std::io::Write;
+
+use basic::{Mflpt, PetsciiString};
+use serde::{ser, Serialize};
+
+use crate::error::{Error, Result};
+use crate::float::representable_value;
+use crate::options::{BinaryOptions, IntegerWidthMethod, StringTerminationMethod};
+
+/// Serializer writing packed binary data after a load address, for machine
+/// language routines that can't afford to parse `DATA` lines.
+pub struct BinarySerializer {
+    options: BinaryOptions,
+    output: Vec<u8>,
+    containers: Vec<BinaryContainer>,
+}
+
+/// Sequence or map being serialized, with the position of its length prefix
+/// when it has to be filled in after counting the elements.
+struct BinaryContainer {
+    expected_len: Option<usize>,
+    len: usize,
+    prefix_position: Option<usize>,
+}
+
+pub fn to_binary_writer<W, T>(mut writer: W, value: &T, options: BinaryOptions) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    writer.write_all(&to_binary_vec(value, options)?)?;
+    Ok(())
+}
+
+pub fn to_binary_vec<T>(value: &T, options: BinaryOptions) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = BinarySerializer::new(options)?;
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_bytes())
+}
+
+/// Checks that `v` fits into `width` bytes.
+fn check_unsigned_width(v: u128, width: usize) -> Result<()> {
+    if width < 16 && v >> (width * 8) != 0 {
+        return Err(Error::UnrepresentableInteger(v.to_string()));
+    }
+    Ok(())
+}
+
+impl BinarySerializer {
+    pub fn new(options: BinaryOptions) -> Result<Self> {
+        options.validate()?;
+
+        Ok(Self {
+            options,
+            output: options.load_address.to_le_bytes().to_vec(),
+            containers: vec![],
+        })
+    }
+
+    /// Returns the load address followed by the serialized data.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.output
+    }
+
+    fn write_unsigned(&mut self, v: u128, width: usize) -> Result<()> {
+        check_unsigned_width(v, width)?;
+        self.output.extend(&v.to_le_bytes()[..width]);
+        Ok(())
+    }
+
+    fn write_signed(&mut self, v: i128, width: usize) -> Result<()> {
+        if width < 16 && (v >> (width * 8 - 1) != 0 && v >> (width * 8 - 1) != -1) {
+            return Err(Error::UnrepresentableInteger(v.to_string()));
+        }
+
+        self.output.extend(&v.to_le_bytes()[..width]);
+        Ok(())
+    }
+
+    fn integer_width(&self, native_width: usize) -> usize {
+        match self.options.integer_width_method {
+            IntegerWidthMethod::Native => native_width,
+            IntegerWidthMethod::Fixed(width) => width,
+        }
+    }
+
+    fn write_length(&mut self, len: usize) -> Result<()> {
+        self.write_unsigned(len as u128, self.options.length_width)
+    }
+
+    /// Writes the length prefix of a sequence or map, or leaves room for it
+    /// when serde doesn't know the length up front.
+    fn begin_container(&mut self, len: Option<usize>, length_prefix: bool) -> Result<()> {
+        let mut prefix_position = None;
+
+        if length_prefix {
+            match len {
+                Some(len) => self.write_length(len)?,
+                None => {
+                    prefix_position = Some(self.output.len());
+                    self.output.extend(vec![0x00; self.options.length_width]);
+                }
+            }
+        }
+
+        self.containers.push(BinaryContainer {
+            expected_len: len.filter(|_| length_prefix),
+            len: 0,
+            prefix_position,
+        });
+        Ok(())
+    }
+
+    fn count_container_element(&mut self) {
+        if let Some(container) = self.containers.last_mut() {
+            container.len += 1;
+        }
+    }
+
+    fn end_container(&mut self) -> Result<()> {
+        let container = self.containers.pop().expect("container stack is empty");
+
+        if let Some(expected_len) = container.expected_len {
+            if expected_len != container.len {
+                return Err(Error::LengthMismatch {
+                    expected: expected_len,
+                    actual: container.len,
+                });
+            }
+        }
+
+        if let Some(position) = container.prefix_position {
+            let width = self.options.length_width;
+            let len = container.len as u128;
+            check_unsigned_width(len, width)?;
+            self.output[position..position + width].copy_from_slice(&len.to_le_bytes()[..width]);
+        }
+        Ok(())
+    }
+
+    fn write_string(&mut self, v: &str) -> Result<()> {
+        let mut string_bin = PetsciiString::from(v).to_petscii(&self.options.encoding_options);
+
+        match self.options.string_termination_method {
+            StringTerminationMethod::LengthPrefix => {
+                self.write_length(string_bin.len())?;
+            }
+            StringTerminationMethod::ZeroTerminated => {
+                string_bin.push(0x00);
+            }
+            StringTerminationMethod::HighBitTerminated => {
+                if string_bin.is_empty() || string_bin.iter().any(|b| b & 0x80 != 0) {
+                    return Err(Error::UnsupportedType(format!(
+                        "string \"{}\" can't be high-bit terminated",
+                        v
+                    )));
+                }
+                *string_bin.last_mut().unwrap() |= 0x80;
+            }
+        }
+
+        self.output.extend(string_bin);
+        Ok(())
+    }
+}
+
+impl ser::Serializer for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.write_signed(v as i128, self.integer_width(1))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.write_signed(v as i128, self.integer_width(2))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.write_signed(v as i128, self.integer_width(4))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_signed(v as i128, self.integer_width(8))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write_signed(v, self.integer_width(16))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write_unsigned(v as u128, self.integer_width(1))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.write_unsigned(v as u128, self.integer_width(2))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.write_unsigned(v as u128, self.integer_width(4))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write_unsigned(v as u128, self.integer_width(8))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write_unsigned(v, self.integer_width(16))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        let v = representable_value(v, self.options.float_overflow_method)?;
+        self.output.extend(Mflpt::from_f64(v)?.to_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.output
+            .extend(PetsciiString(v.to_string()).to_petscii(&self.options.encoding_options));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if self.options.container_prefix_options.sequence_length {
+            self.write_length(v.len())?;
+        }
+
+        self.output.extend(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit_variant("Option", 0, "None")
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.serialize_unit_variant("Option", 1, "Some")?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<()> {
+        self.write_length(variant_index as usize)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.write_length(variant_index as usize)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.begin_container(len, self.options.container_prefix_options.sequence_length)?;
+
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        if self.options.container_prefix_options.tuple_length {
+            self.write_length(len)?;
+        }
+
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.write_length(variant_index as usize)?;
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.begin_container(len, self.options.container_prefix_options.map_length)?;
+
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.write_length(variant_index as usize)?;
+
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.count_container_element();
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_container()
+    }
+}
+
+impl ser::SerializeTuple for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.count_container_element();
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_container()
+    }
+}
+
+impl ser::SerializeStruct for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut BinarySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{ser, Serialize};
+
+    use crate::binary::to_binary_vec;
+    use crate::error::Error;
+    use crate::options::{BinaryOptions, ContainerPrefixOptions, IntegerWidthMethod, StringTerminationMethod};
+
+    #[test]
+    pub fn test_binary() {
+        #[derive(Serialize)]
+        enum Shape {
+            Point,
+            Circle { radius: f32 },
+        }
+
+        #[derive(Serialize)]
+        struct Sprite {
+            name: &'static str,
+            x: u16,
+            y: i8,
+            visible: bool,
+            shapes: Vec<Shape>,
+        }
+
+        let data = Sprite {
+            name: "BALL",
+            x: 320,
+            y: -2,
+            visible: true,
+            shapes: vec![Shape::Point, Shape::Circle { radius: 10.0 }],
+        };
+
+        assert_eq!(
+            to_binary_vec(&data, BinaryOptions::default()).unwrap(),
+            vec![
+                0x00, 0xC0, 0x04, 0x42, 0x41, 0x4C, 0x4C, 0x40, 0x01, 0xFE, 0x01, 0x02, 0x00, 0x01, 0x84, 0x20, 0x00,
+                0x00, 0x00
+            ]
+        );
+
+        let options = BinaryOptions {
+            load_address: 0x2000,
+            integer_width_method: IntegerWidthMethod::Fixed(2),
+            length_width: 2,
+            string_termination_method: StringTerminationMethod::ZeroTerminated,
+            container_prefix_options: ContainerPrefixOptions {
+                map_length: false,
+                ..ContainerPrefixOptions::default()
+            },
+            ..BinaryOptions::default()
+        };
+
+        let data = (-1i8, "HI", BTreeMap::from([(1u8, 'A')]), Some(3u32));
+        assert_eq!(
+            to_binary_vec(&data, options).unwrap(),
+            vec![0x00, 0x20, 0xFF, 0xFF, 0x48, 0x49, 0x00, 0x01, 0x00, 0x41, 0x01, 0x00, 0x03, 0x00]
+        );
+
+        assert!(matches!(
+            to_binary_vec(&70000u32, options),
+            Err(Error::UnrepresentableInteger(_))
+        ));
+        assert!(matches!(
+            to_binary_vec(&-32769i32, options),
+            Err(Error::UnrepresentableInteger(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_binary_strings() {
+        let options = BinaryOptions {
+            string_termination_method: StringTerminationMethod::HighBitTerminated,
+            ..BinaryOptions::default()
+        };
+
+        assert_eq!(to_binary_vec(&"HI", options).unwrap(), vec![0x00, 0xC0, 0x48, 0xC9]);
+        assert!(matches!(to_binary_vec(&"", options), Err(Error::UnsupportedType(_))));
+
+        struct Countdown(u8);
+
+        impl Serialize for Countdown {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_seq((1..=self.0).rev().filter(|v| v % 2 == 1))
+            }
+        }
+
+        assert_eq!(
+            to_binary_vec(&(Countdown(5), 0xAAu8), BinaryOptions::default()).unwrap(),
+            vec![0x00, 0xC0, 0x03, 0x05, 0x03, 0x01, 0xAA]
+        );
+    }
+}
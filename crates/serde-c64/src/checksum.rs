@@ -0,0 +1,240 @@
+use basic::{BasicDialect, BasicLine};
+
+use crate::error::{Error, Result};
+use crate::loader::assemble_subroutine;
+use crate::options::{ChecksumMethod, LoaderOptions, Options};
+
+const CHECKSUM_MODULUS: u32 = 65521;
+
+/// Room taken up by a per-line checksum item, `, 65520` at most.
+pub const CHECKSUM_ITEM_SIZE: usize = 7;
+
+const PETSCII_QUOTE: u8 = 0x22;
+const PETSCII_COMMA: u8 = 0x2C;
+const PETSCII_COLON: u8 = 0x3A;
+
+/// Checksum of the text of a `DATA` line following the `DATA` keyword.
+///
+/// Doubles the checksum before adding each character, so swapped characters
+/// are caught along with mistyped ones.
+pub fn line_checksum(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |checksum, &b| (checksum * 2 + b as u32) % CHECKSUM_MODULUS)
+}
+
+/// Checks the per-line checksum of a `DATA` line the same way the generated
+/// verifier does: the digits of the last item must match the checksum of the
+/// text before the last comma.
+pub fn verify_line_checksum(line_number: u16, bytes: &[u8]) -> Result<()> {
+    let mut checksum = 0;
+    let mut expected_checksum = None;
+    let mut value = 0u64;
+    let mut quoted = false;
+
+    for &b in bytes.iter().skip(1) {
+        if b == PETSCII_COLON && !quoted {
+            break;
+        }
+        if b == PETSCII_QUOTE {
+            quoted = !quoted;
+        }
+        if b == PETSCII_COMMA && !quoted {
+            expected_checksum = Some(checksum);
+            value = 0;
+        }
+        if b.is_ascii_digit() {
+            value = value.saturating_mul(10).saturating_add((b - b'0') as u64);
+        }
+        checksum = (checksum * 2 + b as u32) % CHECKSUM_MODULUS;
+    }
+
+    match expected_checksum {
+        Some(checksum) if checksum as u64 == value => Ok(()),
+        _ => Err(Error::ChecksumMismatch(line_number)),
+    }
+}
+
+/// Generates a BASIC subroutine that walks the program in memory, recomputes
+/// the checksums of the first `line_count` `DATA` lines and compares them
+/// with the per-line checksum items or the checksum table.
+///
+/// The first line jumps over the subroutine, so running into it does no
+/// harm. It returns with `ZE` set to the first bad line number, or 0.
+pub fn generate_verifier(
+    options: &Options,
+    loader_options: &LoaderOptions,
+    line_count: usize,
+    item_count: usize,
+) -> Result<Vec<BasicLine>> {
+    loader_options.validate()?;
+
+    let dialect = options.machine.dialect();
+    let data_token = dialect
+        .keyword_bytes("DATA")
+        .expect("the BASIC of every machine has DATA")[0];
+    let basic_start_pointer = options.machine.basic_start_pointer();
+    let mut listing: Vec<(&str, String)> = vec![
+        ("", String::from("GOTO #END#")),
+        (
+            "",
+            format!(
+                "ZE=0:ZK=0:ZP=PEEK({})+256*PEEK({})",
+                basic_start_pointer,
+                basic_start_pointer + 1
+            ),
+        ),
+    ];
+
+    if options.checksum_method == ChecksumMethod::Table && item_count > 0 {
+        listing.push(("", format!("RESTORE:FOR ZI=1 TO {}:READ ZX$:NEXT", item_count)));
+    }
+
+    listing.extend([
+        (
+            "LINE",
+            format!("ZN=PEEK(ZP)+256*PEEK(ZP+1):IF ZN=0 OR ZK={} THEN #DONE#", line_count),
+        ),
+        (
+            "",
+            format!(
+                "ZL=PEEK(ZP+2)+256*PEEK(ZP+3):IF ZL<{} OR PEEK(ZP+4)<>{} THEN ZP=ZN:GOTO #LINE#",
+                options.line_number_start, data_token
+            ),
+        ),
+        ("", String::from("ZK=ZK+1:ZC=0:ZS=0:ZQ=0:ZV=0:ZI=ZP+5")),
+        (
+            "CHAR",
+            String::from("ZB=PEEK(ZI):IF ZB=0 OR (ZB=58 AND ZQ=0) THEN #CHECK#"),
+        ),
+        ("", String::from("IF ZB=34 THEN ZQ=1-ZQ")),
+    ]);
+
+    let check = match options.checksum_method {
+        ChecksumMethod::PerLine => {
+            listing.extend([
+                ("", String::from("IF ZB=44 AND ZQ=0 THEN ZS=ZC:ZV=0")),
+                ("", String::from("IF ZB>47 AND ZB<58 THEN ZV=ZV*10+ZB-48")),
+            ]);
+            "IF ZS<>ZV THEN"
+        }
+        _ => "READ ZV:IF ZC<>ZV THEN",
+    };
+
+    listing.extend([
+        (
+            "",
+            format!("ZC=ZC*2+ZB:ZC=ZC-INT(ZC/{0})*{0}:ZI=ZI+1:GOTO #CHAR#", CHECKSUM_MODULUS),
+        ),
+        (
+            "CHECK",
+            format!("{} ZE=ZL:PRINT \"CHECKSUM ERROR IN\";ZL:GOTO #DONE#", check),
+        ),
+        ("", String::from("ZP=ZN:GOTO #LINE#")),
+        ("DONE", String::from("RESTORE:RETURN")),
+        ("END", String::from("REM")),
+    ]);
+
+    assemble_subroutine(
+        options,
+        loader_options,
+        &listing,
+        "verifier lines overlap the DATA lines",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use basic::{BasicDialect, BasicToken, Machine, PetsciiEncodingOptions};
+
+    use crate::checksum::{generate_verifier, line_checksum, verify_line_checksum};
+    use crate::error::Error;
+    use crate::options::{ChecksumMethod, LoaderOptions, Options};
+
+    #[test]
+    pub fn test_line_checksum() {
+        assert_eq!(line_checksum(b""), 0);
+        assert_eq!(line_checksum(b" 1"), 0x20 * 2 + 0x31);
+        assert_ne!(line_checksum(b" 12, 21"), line_checksum(b" 21, 12"));
+        assert!(line_checksum(&[0xFF; 100]) < 65521);
+
+        let line = |text: &str| {
+            let mut bytes = vec![0x83];
+            bytes.extend(text.as_bytes());
+            bytes
+        };
+
+        let checksum = line_checksum(b" 1, \"A,B\"");
+        assert!(verify_line_checksum(1000, &line(&format!(" 1, \"A,B\", {}", checksum))).is_ok());
+        assert!(verify_line_checksum(1000, &line(&format!(" 1, \"A,B\", {}:REM X, 1", checksum))).is_ok());
+        assert!(matches!(
+            verify_line_checksum(1000, &line(&format!(" 1, \"A.B\", {}", checksum))),
+            Err(Error::ChecksumMismatch(1000))
+        ));
+        assert!(matches!(
+            verify_line_checksum(1010, &line(" 1")),
+            Err(Error::ChecksumMismatch(1010))
+        ));
+    }
+
+    #[test]
+    pub fn test_generate_verifier() {
+        let listing = |options: &Options| {
+            generate_verifier(options, &LoaderOptions::default(), 12, 34)
+                .unwrap()
+                .iter()
+                .map(|line| {
+                    let text = line
+                        .1
+                        .iter()
+                        .map(|token| match token {
+                            BasicToken::Raw(_) => {
+                                String::from_utf8(token.to_bytes(&PetsciiEncodingOptions::default())).unwrap()
+                            }
+                            BasicToken::Keyword(_) | BasicToken::Bytes(_) => {
+                                let bytes = token.to_bytes(&PetsciiEncodingOptions::default());
+                                format!("{{{}}}", options.machine.dialect().keyword_name(&bytes).unwrap().0)
+                            }
+                        })
+                        .collect::<String>();
+                    (line.0, text)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let options = Options {
+            checksum_method: ChecksumMethod::PerLine,
+            ..Options::default()
+        };
+        let lines = listing(&options);
+        assert_eq!(lines.len(), 14);
+        assert_eq!(lines[0], (10, String::from("{GOTO} 140")));
+        assert_eq!(lines[13], (140, String::from("{REM}")));
+        assert!(lines[2].1.ends_with("ZK{=}12 {THEN} 130"));
+
+        let options = Options {
+            checksum_method: ChecksumMethod::Table,
+            ..Options::default()
+        };
+        let lines = listing(&options);
+        assert_eq!(lines.len(), 13);
+        assert!(lines[1].1.ends_with("{PEEK}(43){+}256{*}{PEEK}(44)"));
+        assert!(lines[2].1.contains("ZI{=}1 {TO} 34"));
+
+        let options = Options {
+            machine: Machine::C128,
+            ..options
+        };
+        assert!(listing(&options)[1].1.contains("{PEEK}(45)"));
+
+        let options = Options {
+            checksum_method: ChecksumMethod::Table,
+            line_number_start: 100,
+            ..Options::default()
+        };
+        assert!(matches!(
+            generate_verifier(&options, &LoaderOptions::default(), 1, 1),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+}
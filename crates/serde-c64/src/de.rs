@@ -0,0 +1,827 @@
+use std::io::Read;
+
+use basic::{BasicDialect, PetsciiString};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::checksum::verify_line_checksum;
+use crate::error::{Error, Result};
+use crate::options::{ChecksumMethod, ContainerTerminationMethod, LargeIntegerMethod, Options, StringSplitMethod};
+use crate::packed::{bits_per_digit, packed_length, unpack_bytes};
+
+const PETSCII_QUOTE: u8 = 0x22;
+const PETSCII_COMMA: u8 = 0x2C;
+const PETSCII_COLON: u8 = 0x3A;
+const PETSCII_SPACE: u8 = 0x20;
+
+#[derive(Debug)]
+struct DataItem {
+    line_number: u16,
+    text: String,
+    quoted: bool,
+}
+
+pub struct Deserializer {
+    options: Options,
+    data_items: Vec<DataItem>,
+    data_position: usize,
+}
+
+pub fn from_slice<T>(bytes: &[u8], options: Options) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_slice(bytes, options)?;
+    T::deserialize(&mut deserializer)
+}
+
+pub fn from_reader<R, T>(mut reader: R, options: Options) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    from_slice(&bytes, options)
+}
+
+impl Deserializer {
+    pub fn from_slice(bytes: &[u8], options: Options) -> Result<Self> {
+        let dialect = options.machine.dialect();
+        let data_token = dialect
+            .keyword_bytes("DATA")
+            .expect("the BASIC of every machine has DATA");
+
+        let mut data_items = vec![];
+        for (line_number, line_bytes) in read_basic_lines(bytes)? {
+            read_data_items(line_number, line_bytes, data_token, &options, &mut data_items);
+
+            // Per-line checksums are the last data item of the `DATA` lines
+            let data_line = line_number >= options.line_number_start && line_bytes.starts_with(data_token);
+            if options.checksum_method == ChecksumMethod::PerLine && data_line {
+                verify_line_checksum(line_number, line_bytes)?;
+                data_items.pop();
+            }
+        }
+
+        Ok(Self {
+            options,
+            data_items,
+            data_position: 0,
+        })
+    }
+
+    fn next_item(&mut self) -> Result<&DataItem> {
+        let item = self.data_items.get(self.data_position).ok_or(Error::OutOfData)?;
+        self.data_position += 1;
+        Ok(item)
+    }
+
+    fn peek_item(&self) -> Option<&DataItem> {
+        self.data_items.get(self.data_position)
+    }
+
+    fn has_remaining_items(&self) -> bool {
+        self.data_position < self.data_items.len()
+    }
+
+    /// Skips the sentinel when it's the next data item under
+    /// `ContainerTerminationMethod::Sentinel`.
+    fn consume_sentinel(&mut self) -> bool {
+        let ContainerTerminationMethod::Sentinel(sentinel) = self.options.container_termination_method else {
+            return false;
+        };
+
+        let found = self
+            .peek_item()
+            .is_some_and(|item| sentinel.matches(&item.text, &self.options.encoding_options));
+        if found {
+            self.data_position += 1;
+        }
+        found
+    }
+
+    /// Checks whether a sequence without a length prefix has more elements.
+    fn has_next_unprefixed_element(&mut self) -> bool {
+        !self.consume_sentinel() && self.has_remaining_items()
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        Ok(self.next_item()?.text.clone())
+    }
+
+    /// Reads a string value, joining its chunks under `StringSplitMethod::Chunked`.
+    fn read_string_value(&mut self) -> Result<String> {
+        match self.options.string_split_method {
+            StringSplitMethod::Disabled => self.read_string(),
+            StringSplitMethod::Chunked => {
+                let chunk_count = self.read_length()?;
+
+                let mut s = String::new();
+                for _ in 0..chunk_count {
+                    s.push_str(&self.read_string()?);
+                }
+                Ok(s)
+            }
+        }
+    }
+
+    fn read_float(&mut self) -> Result<f64> {
+        let item = self.next_item()?;
+        parse_float(&item.text).ok_or_else(|| item.invalid())
+    }
+
+    fn read_length(&mut self) -> Result<usize> {
+        let item = self.next_item()?;
+        parse_integer(&item.text)
+            .and_then(|v| usize::try_from(v).ok())
+            .ok_or_else(|| item.invalid())
+    }
+
+    /// Reads an enum variant index or name. Names are matched case-insensitively
+    /// as the unshifted character set can't tell letter cases apart.
+    fn read_variant_tag(&mut self, variants: &'static [&'static str]) -> Result<VariantTag> {
+        if self.options.emit_enum_names {
+            let name = self.read_string()?;
+            let variant = variants
+                .iter()
+                .find(|v| **v == name)
+                .or_else(|| variants.iter().find(|v| v.eq_ignore_ascii_case(&name)));
+
+            Ok(variant.map_or(VariantTag::Name(name), |v| VariantTag::Name(v.to_string())))
+        } else {
+            let item = self.next_item()?;
+            parse_integer(&item.text)
+                .and_then(|v| u32::try_from(v).ok())
+                .map(VariantTag::Index)
+                .ok_or_else(|| item.invalid())
+        }
+    }
+
+    /// Reads a signed integer written by `emit_signed_integer`.
+    fn read_signed_integer(&mut self, word_count: usize) -> Result<i128> {
+        let large_integer_method = self.options.large_integer_method;
+
+        let item = self.next_item()?;
+        let first_word = match large_integer_method {
+            LargeIntegerMethod::String if word_count > 1 => item.text.trim().parse::<i128>().ok(),
+            _ => parse_integer(&item.text),
+        };
+        let mut value = first_word.ok_or_else(|| item.invalid())?;
+
+        if large_integer_method == LargeIntegerMethod::Words {
+            for _ in 1..word_count {
+                value = (value << 32) | self.read_word()? as i128;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Reads an unsigned integer written by `emit_unsigned_integer`.
+    fn read_unsigned_integer(&mut self, word_count: usize) -> Result<u128> {
+        let large_integer_method = self.options.large_integer_method;
+
+        let item = self.next_item()?;
+        let first_word = match large_integer_method {
+            LargeIntegerMethod::String if word_count > 1 => item.text.trim().parse::<u128>().ok(),
+            _ => parse_integer(&item.text).and_then(|v| u128::try_from(v).ok()),
+        };
+        let mut value = first_word.ok_or_else(|| item.invalid())?;
+
+        if large_integer_method == LargeIntegerMethod::Words {
+            for _ in 1..word_count {
+                value = (value << 32) | self.read_word()? as u128;
+            }
+        }
+        Ok(value)
+    }
+
+    fn read_word(&mut self) -> Result<u32> {
+        let item = self.next_item()?;
+        parse_integer(&item.text)
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| item.invalid())
+    }
+
+    fn check_tuple_length(&mut self, len: usize) -> Result<()> {
+        if self.options.container_prefix_options.tuple_length {
+            let item = self.next_item()?;
+            if parse_integer(&item.text) != Some(len as i128) {
+                return Err(item.invalid());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DataItem {
+    fn invalid(&self) -> Error {
+        Error::InvalidDataItem {
+            line_number: self.line_number,
+            item: self.text.clone(),
+        }
+    }
+}
+
+enum VariantTag {
+    Index(u32),
+    Name(String),
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Splits a PRG file into its line numbers and line contents.
+///
+/// Line links are not trusted, lines are read sequentially the same way BASIC
+/// relinks the program after loading it.
+fn read_basic_lines(bytes: &[u8]) -> Result<Vec<(u16, &[u8])>> {
+    let mut lines = vec![];
+    let mut rest = bytes.get(2..).ok_or(Error::MalformedProgram)?;
+
+    loop {
+        match rest {
+            [0x00, 0x00, ..] => break,
+            [_, _, line_lo, line_hi, line_contents @ ..] => {
+                let line_length = line_contents
+                    .iter()
+                    .position(|&b| b == 0x00)
+                    .ok_or(Error::MalformedProgram)?;
+
+                lines.push((u16::from_le_bytes([*line_lo, *line_hi]), &line_contents[..line_length]));
+                rest = &line_contents[line_length + 1..];
+            }
+            _ => return Err(Error::MalformedProgram),
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Collects the items of every `DATA` statement in a line, following the
+/// rules of the `READ` command. `data_token` is the `DATA` keyword of the
+/// dialect.
+fn read_data_items(
+    line_number: u16,
+    bytes: &[u8],
+    data_token: &[u8],
+    options: &Options,
+    data_items: &mut Vec<DataItem>,
+) {
+    let decode = |bytes: &[u8]| PetsciiString::from_petscii(bytes, &options.encoding_options).0;
+    let skip_spaces = |mut pos: usize| {
+        while bytes.get(pos) == Some(&PETSCII_SPACE) {
+            pos += 1;
+        }
+        pos
+    };
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        pos = skip_spaces(pos);
+
+        if bytes[pos..].starts_with(data_token) {
+            pos += data_token.len();
+
+            loop {
+                pos = skip_spaces(pos);
+
+                let item = if bytes.get(pos) == Some(&PETSCII_QUOTE) {
+                    let start = pos + 1;
+                    let end = bytes[start..]
+                        .iter()
+                        .position(|&b| b == PETSCII_QUOTE)
+                        .map_or(bytes.len(), |p| start + p);
+
+                    pos = end + 1;
+                    while pos < bytes.len() && bytes[pos] != PETSCII_COMMA && bytes[pos] != PETSCII_COLON {
+                        pos += 1;
+                    }
+
+                    DataItem {
+                        line_number,
+                        text: decode(&bytes[start..end]),
+                        quoted: true,
+                    }
+                } else {
+                    let start = pos;
+                    while pos < bytes.len() && bytes[pos] != PETSCII_COMMA && bytes[pos] != PETSCII_COLON {
+                        pos += 1;
+                    }
+
+                    DataItem {
+                        line_number,
+                        text: decode(&bytes[start..pos]),
+                        quoted: false,
+                    }
+                };
+
+                data_items.push(item);
+
+                if bytes.get(pos) == Some(&PETSCII_COMMA) {
+                    pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        // Skip to the next statement
+        let mut quoted = false;
+        while pos < bytes.len() && (quoted || bytes[pos] != PETSCII_COLON) {
+            if bytes[pos] == PETSCII_QUOTE {
+                quoted = !quoted;
+            }
+            pos += 1;
+        }
+        pos += 1;
+    }
+}
+
+/// Parses a number the way `READ` does: spaces are ignored and an empty item
+/// reads as zero.
+pub(crate) fn parse_float(s: &str) -> Option<f64> {
+    let s = s.replace(' ', "");
+    if s.is_empty() {
+        Some(0.0)
+    } else {
+        s.parse::<f64>().ok().filter(|v| v.is_finite())
+    }
+}
+
+fn parse_integer(s: &str) -> Option<i128> {
+    let s = s.replace(' ', "");
+    if let Ok(v) = s.strip_prefix('+').unwrap_or(&s).parse::<i128>() {
+        Some(v)
+    } else {
+        parse_float(&s).filter(|v| v.fract() == 0.0).map(|v| v as i128)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+macro_rules! deserialize_integer {
+    ($method:ident, $visit:ident, $ty:ty, $read:ident, $word_count:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            let line_number = self.peek_item().map_or(0, |item| item.line_number);
+            let value = self.$read($word_count)?;
+            let value = <$ty>::try_from(value).map_err(|_| Error::InvalidDataItem {
+                line_number,
+                item: value.to_string(),
+            })?;
+            visitor.$visit(value)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let item = self.next_item()?;
+        if item.quoted {
+            visitor.visit_string(item.text.clone())
+        } else if let Some(v) = parse_integer(&item.text).and_then(|v| i64::try_from(v).ok()) {
+            visitor.visit_i64(v)
+        } else if let Some(v) = parse_float(&item.text) {
+            visitor.visit_f64(v)
+        } else {
+            visitor.visit_string(item.text.clone())
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bool(self.read_float()? != 0.0)
+    }
+
+    deserialize_integer!(deserialize_i8, visit_i8, i8, read_signed_integer, 1);
+    deserialize_integer!(deserialize_i16, visit_i16, i16, read_signed_integer, 1);
+    deserialize_integer!(deserialize_i32, visit_i32, i32, read_signed_integer, 1);
+    deserialize_integer!(deserialize_i64, visit_i64, i64, read_signed_integer, 2);
+    deserialize_integer!(deserialize_u8, visit_u8, u8, read_unsigned_integer, 1);
+    deserialize_integer!(deserialize_u16, visit_u16, u16, read_unsigned_integer, 1);
+    deserialize_integer!(deserialize_u32, visit_u32, u32, read_unsigned_integer, 1);
+    deserialize_integer!(deserialize_u64, visit_u64, u64, read_unsigned_integer, 2);
+    deserialize_integer!(deserialize_i128, visit_i128, i128, read_signed_integer, 4);
+    deserialize_integer!(deserialize_u128, visit_u128, u128, read_unsigned_integer, 4);
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_f32(self.read_float()? as f32)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_f64(self.read_float()?)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let item = self.next_item()?;
+        let mut chars = item.text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(item.invalid()),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.read_string_value()?)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.read_string_value()?)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(bits) = bits_per_digit(self.options.byte_encoding_method) {
+            let item = self.next_item()?;
+            let len = parse_integer(&item.text)
+                .and_then(|v| usize::try_from(v).ok())
+                .ok_or_else(|| item.invalid())?;
+
+            let mut packed = String::new();
+            let mut invalid = item.invalid();
+            while packed.chars().count() < packed_length(len, bits) {
+                let item = self.next_item()?;
+                packed.push_str(&item.text);
+                invalid = item.invalid();
+            }
+
+            let bytes = unpack_bytes(&packed, len, bits).ok_or(invalid)?;
+            return visitor.visit_byte_buf(bytes);
+        }
+
+        let len = if self.options.container_prefix_options.sequence_length {
+            Some(self.read_length()?)
+        } else {
+            None
+        };
+
+        let mut bytes = vec![];
+        while len.map_or_else(|| self.has_next_unprefixed_element(), |len| bytes.len() < len) {
+            let item = self.next_item()?;
+            bytes.push(
+                parse_integer(&item.text)
+                    .and_then(|v| u8::try_from(v).ok())
+                    .ok_or_else(|| item.invalid())?,
+            );
+        }
+
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let line_number = self.peek_item().map_or(0, |item| item.line_number);
+        match self.read_variant_tag(&["None", "Some"])? {
+            VariantTag::Index(0) => visitor.visit_none(),
+            VariantTag::Index(1) => visitor.visit_some(self),
+            VariantTag::Name(name) if name == "None" => visitor.visit_none(),
+            VariantTag::Name(name) if name == "Some" => visitor.visit_some(self),
+            VariantTag::Index(index) => Err(Error::InvalidDataItem {
+                line_number,
+                item: index.to_string(),
+            }),
+            VariantTag::Name(name) => Err(Error::InvalidDataItem {
+                line_number,
+                item: name,
+            }),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let len = if self.options.container_prefix_options.sequence_length {
+            Some(self.read_length()?)
+        } else {
+            None
+        };
+
+        visitor.visit_seq(DataAccess {
+            de: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.check_tuple_length(len)?;
+        visitor.visit_seq(DataAccess {
+            de: self,
+            remaining: Some(len),
+        })
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let len = if self.options.container_prefix_options.map_length {
+            Some(self.read_length()?)
+        } else {
+            None
+        };
+
+        visitor.visit_map(DataAccess {
+            de: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(DataAccess {
+            de: self,
+            remaining: Some(fields.len()),
+        })
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(EnumAccess { de: self, variants })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.next_item()?;
+        visitor.visit_unit()
+    }
+}
+
+struct DataAccess<'a> {
+    de: &'a mut Deserializer,
+    remaining: Option<usize>,
+}
+
+impl DataAccess<'_> {
+    fn has_next_element(&mut self) -> bool {
+        match self.remaining.as_mut() {
+            Some(0) => false,
+            Some(remaining) => {
+                *remaining -= 1;
+                true
+            }
+            None => self.de.has_next_unprefixed_element(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for DataAccess<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.has_next_element() {
+            seed.deserialize(&mut *self.de).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+impl<'de> de::MapAccess<'de> for DataAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.has_next_element() {
+            seed.deserialize(&mut *self.de).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+struct EnumAccess<'a> {
+    de: &'a mut Deserializer,
+    variants: &'static [&'static str],
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = &'a mut Deserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = match self.de.read_variant_tag(self.variants)? {
+            VariantTag::Index(index) => seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?,
+            VariantTag::Name(name) => seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name))?,
+        };
+        Ok((value, self.de))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(DataAccess {
+            de: self,
+            remaining: Some(fields.len()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::de::from_slice;
+    use crate::options::{ContainerPrefixOptions, Options};
+    use crate::ser::to_writer;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Rectangle { width: u16, height: u16 },
+        Line(i8, i8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Scene {
+        name: String,
+        visible: bool,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, Option<u32>>,
+        origin: (i32, i32),
+        initial: char,
+    }
+
+    fn round_trip(options: Options) {
+        let scene = Scene {
+            name: String::from("HELLO, WORLD "),
+            visible: true,
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle(1.5),
+                Shape::Rectangle {
+                    width: 320,
+                    height: 200,
+                },
+                Shape::Line(-1, 1),
+            ],
+            tags: BTreeMap::from([(String::from("A"), Some(64)), (String::from("B"), None)]),
+            origin: (-160, 100),
+            initial: 'X',
+        };
+
+        let mut prg_bin = vec![];
+        to_writer(&mut prg_bin, &scene, options).unwrap();
+        assert_eq!(from_slice::<Scene>(&prg_bin, options).unwrap(), scene);
+    }
+
+    #[test]
+    pub fn test_round_trip() {
+        round_trip(Options::default());
+    }
+
+    #[test]
+    pub fn test_round_trip_enum_names() {
+        round_trip(Options {
+            line_length: 20,
+            container_prefix_options: ContainerPrefixOptions {
+                tuple_length: true,
+                ..ContainerPrefixOptions::default()
+            },
+            emit_enum_names: true,
+            ..Options::default()
+        });
+    }
+
+    #[test]
+    pub fn test_read_rules() {
+        #[rustfmt::skip]
+        let prg_bin = [
+            0x01, 0x08, 0x16, 0x08, 0x0A, 0x00, 0x8F, 0x20, 0x22, 0x3A, 0x83, 0x22, 0x3A, 0x83, 0x20, 0x31, 0x2C,
+            0x20, 0x22, 0x41, 0x2C, 0x22, 0x00, 0x21, 0x08, 0x14, 0x00, 0x99, 0x3A, 0x83, 0x2C, 0x20, 0x32, 0x00,
+            0x00, 0x00,
+        ];
+
+        assert_eq!(
+            from_slice::<(u8, String, f32, u8)>(&prg_bin, Options::default()).unwrap(),
+            (1, String::from("A,"), 0.0, 2)
+        );
+    }
+}
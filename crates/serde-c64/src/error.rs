@@ -1,25 +1,134 @@
 use std::fmt::Display;
 
-use serde::ser;
+use serde::{de, ser};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
-pub enum Error {}
+pub enum Error {
+    /// Reading or writing the program failed.
+    Io(std::io::Error),
+
+    /// Error message produced by a `Serialize` or `Deserialize` implementation.
+    Custom(String),
+
+    /// Error reported by the `basic` crate.
+    Basic(basic::Error),
+
+    /// The options can't be used to generate a valid program.
+    InvalidOptions(&'static str),
+
+    /// A single data item doesn't fit into an empty `DATA` line.
+    ItemTooLongForLine { line_number: u16, item: String },
+
+    /// Ran out of line numbers while emitting `DATA` lines.
+    LineNumberOverflow,
+
+    /// The program leaves less free memory than `MemoryBudgetMethod` asks for
+    /// (`?OUT OF MEMORY ERROR`).
+    ProgramTooLarge { size: usize, available: usize },
+
+    /// The string is longer than a BASIC string variable can hold.
+    StringTooLong(usize),
+
+    /// The float is NaN, infinite or out of the range of Commodore BASIC floats.
+    UnrepresentableFloat(f64),
+
+    /// The integer is too large to be read back exactly by BASIC.
+    UnrepresentableInteger(String),
+
+    /// The type can't be handled with the given options.
+    UnsupportedType(String),
+
+    /// The first data item of a sequence or map element reads as the sentinel
+    /// terminating it.
+    SentinelCollision(String),
+
+    /// A sequence or map announced a different length than the number of
+    /// elements it serialized.
+    LengthMismatch { expected: usize, actual: usize },
+
+    /// The input is not a well-formed tokenized BASIC program.
+    MalformedProgram,
+
+    /// The checksum of a `DATA` line doesn't match its text.
+    ChecksumMismatch(u16),
+
+    /// Tried to read past the last `DATA` item of the program (`?OUT OF DATA ERROR`).
+    OutOfData,
+
+    /// A `DATA` item could not be read as the requested type.
+    InvalidDataItem { line_number: u16, item: String },
+}
 
 impl ser::Error for Error {
     fn custom<T>(msg: T) -> Self
     where
         T: Display,
     {
-        todo!()
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Error::Custom(msg.to_string())
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        todo!()
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Custom(msg) => write!(f, "{}", msg),
+            Error::Basic(err) => write!(f, "{}", err),
+            Error::InvalidOptions(reason) => write!(f, "invalid options: {}", reason),
+            Error::ItemTooLongForLine { line_number, item } => {
+                write!(f, "data item \"{}\" is too long for line {}", item, line_number)
+            }
+            Error::LineNumberOverflow => write!(f, "line number overflow"),
+            Error::ProgramTooLarge { size, available } => {
+                write!(f, "program size {} exceeds the available {} bytes", size, available)
+            }
+            Error::StringTooLong(length) => write!(f, "string of length {} is too long", length),
+            Error::UnrepresentableFloat(v) => write!(f, "float {} can't be represented in BASIC", v),
+            Error::UnrepresentableInteger(v) => write!(f, "integer {} can't be represented in BASIC", v),
+            Error::UnsupportedType(reason) => write!(f, "unsupported type: {}", reason),
+            Error::SentinelCollision(item) => write!(f, "data item \"{}\" collides with the sentinel", item),
+            Error::LengthMismatch { expected, actual } => {
+                write!(f, "expected {} elements, got {}", expected, actual)
+            }
+            Error::MalformedProgram => write!(f, "malformed BASIC program"),
+            Error::ChecksumMismatch(line_number) => write!(f, "checksum mismatch in line {}", line_number),
+            Error::OutOfData => write!(f, "out of data"),
+            Error::InvalidDataItem { line_number, item } => {
+                write!(f, "invalid data item \"{}\" in line {}", item, line_number)
+            }
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Basic(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<basic::Error> for Error {
+    fn from(err: basic::Error) -> Self {
+        Error::Basic(err)
+    }
+}
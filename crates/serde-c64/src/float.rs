@@ -0,0 +1,166 @@
+use basic::Mflpt;
+
+use crate::error::{Error, Result};
+use crate::options::FloatOverflowMethod;
+
+/// Largest magnitude of a 5-byte Microsoft float. Printed with 9 significant
+/// digits it still reads back without overflowing.
+pub const MAX_FLOAT: f64 = Mflpt::MAX;
+
+/// Smallest positive magnitude of a 5-byte Microsoft float, anything closer to
+/// zero reads back as zero.
+pub const MIN_FLOAT: f64 = Mflpt::MIN_POSITIVE;
+
+/// Largest integer magnitude the 32-bit mantissa of a 5-byte Microsoft float
+/// holds exactly.
+pub const MAX_EXACT_INTEGER: u128 = 1 << 32;
+
+const MAX_SIGNIFICANT_DIGITS: usize = 9;
+
+pub fn format_f32(v: f32, method: FloatOverflowMethod) -> Result<String> {
+    // Rust's shortest representation of the `f32` itself, so the value doesn't
+    // pick up noise digits from the conversion to `f64`.
+    format_float(v as f64, format!("{:e}", v), method)
+}
+
+pub fn format_f64(v: f64, method: FloatOverflowMethod) -> Result<String> {
+    format_float(v, format!("{:e}", v), method)
+}
+
+/// Formats a float the way Commodore BASIC prints numbers.
+///
+/// Keeps at most 9 significant digits, uses fixed notation between `.01` and
+/// `999999999`, and `E` notation with a signed two-digit exponent elsewhere.
+fn format_float(v: f64, mut scientific: String, method: FloatOverflowMethod) -> Result<String> {
+    if v.is_nan() || v.abs() > MAX_FLOAT {
+        return format_f64(representable_value(v, method)?, method);
+    }
+
+    if v.abs() < MIN_FLOAT {
+        return Ok(String::from("0"));
+    }
+
+    if significant_digits(&scientific) > MAX_SIGNIFICANT_DIGITS {
+        scientific = format!("{:.*e}", MAX_SIGNIFICANT_DIGITS - 1, v);
+    }
+
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent = exponent.parse::<i32>().unwrap();
+    let sign = if mantissa.starts_with('-') { "-" } else { "" };
+    let digits = mantissa
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>()
+        .trim_end_matches('0')
+        .to_string();
+
+    let formatted = match exponent {
+        0..=8 => {
+            let integer_length = exponent as usize + 1;
+            if digits.len() > integer_length {
+                format!("{}.{}", &digits[..integer_length], &digits[integer_length..])
+            } else {
+                format!("{:0<width$}", digits, width = integer_length)
+            }
+        }
+        -2..=-1 => format!(".{}{}", "0".repeat((-exponent - 1) as usize), digits),
+        _ => {
+            let fraction = if digits.len() > 1 {
+                format!(".{}", &digits[1..])
+            } else {
+                String::new()
+            };
+            let exponent_sign = if exponent < 0 { '-' } else { '+' };
+            format!("{}{}E{}{:02}", &digits[..1], fraction, exponent_sign, exponent.abs())
+        }
+    };
+
+    Ok(format!("{}{}", sign, formatted))
+}
+
+/// Picks the value written in place of NaN, infinities and out-of-range values.
+pub(crate) fn representable_value(v: f64, method: FloatOverflowMethod) -> Result<f64> {
+    if !v.is_nan() && v.abs() <= MAX_FLOAT {
+        return Ok(v);
+    }
+
+    match method {
+        FloatOverflowMethod::Fail => Err(Error::UnrepresentableFloat(v)),
+        FloatOverflowMethod::Clamp if v.is_nan() => Err(Error::UnrepresentableFloat(v)),
+        FloatOverflowMethod::Clamp => Ok(MAX_FLOAT.copysign(v)),
+        FloatOverflowMethod::Sentinel(sentinel) if sentinel.is_finite() && sentinel.abs() <= MAX_FLOAT => Ok(sentinel),
+        FloatOverflowMethod::Sentinel(_) => Err(Error::UnrepresentableFloat(v)),
+    }
+}
+
+fn significant_digits(scientific: &str) -> usize {
+    let (mantissa, _) = scientific.split_once('e').unwrap();
+    mantissa
+        .trim_end_matches('0')
+        .chars()
+        .filter(char::is_ascii_digit)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use basic::Mflpt;
+
+    use crate::error::Error;
+    use crate::float::{format_f32, format_f64};
+    use crate::options::FloatOverflowMethod;
+
+    #[test]
+    pub fn test_format_float() {
+        let format = |v| format_f64(v, FloatOverflowMethod::Fail).unwrap();
+
+        assert_eq!(format(0.0), "0");
+        assert_eq!(format(-0.0), "0");
+        assert_eq!(format(1.0), "1");
+        assert_eq!(format(-1.0), "-1");
+        assert_eq!(format(0.5), ".5");
+        assert_eq!(format(-0.5), "-.5");
+        assert_eq!(format(0.01), ".01");
+        assert_eq!(format(0.001), "1E-03");
+        assert_eq!(format(-0.00125), "-1.25E-03");
+        assert_eq!(format(1868.0), "1868");
+        assert_eq!(format(35.7642), "35.7642");
+        assert_eq!(format(123456789.0), "123456789");
+        assert_eq!(format(1234567890.0), "1.23456789E+09");
+        assert_eq!(format(1e9), "1E+09");
+        assert_eq!(format(std::f64::consts::PI), "3.14159265");
+        assert_eq!(format(2.0 / 3.0), ".666666667");
+        assert_eq!(format(999999999.7), "1E+09");
+        assert_eq!(format(1e-40), "0");
+        assert_eq!(format(1.70141183e38), "1.70141183E+38");
+        assert_eq!(format(Mflpt::MAX), "1.70141183E+38");
+        assert_eq!(format(Mflpt::MIN_POSITIVE), "2.93873588E-39");
+
+        let format = |v| format_f32(v, FloatOverflowMethod::Fail).unwrap();
+
+        assert_eq!(format(1.1), "1.1");
+        assert_eq!(format(140.3849), "140.3849");
+        assert_eq!(format(1e20), "1E+20");
+    }
+
+    #[test]
+    pub fn test_format_float_overflow() {
+        for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.8e38] {
+            assert!(matches!(
+                format_f64(v, FloatOverflowMethod::Fail),
+                Err(Error::UnrepresentableFloat(_))
+            ));
+        }
+
+        let format = |v| format_f64(v, FloatOverflowMethod::Clamp);
+
+        assert_eq!(format(f64::INFINITY).unwrap(), "1.70141183E+38");
+        assert_eq!(format(-1e39).unwrap(), "-1.70141183E+38");
+        assert!(format(f64::NAN).is_err());
+
+        let format = |v| format_f64(v, FloatOverflowMethod::Sentinel(-1.0));
+
+        assert_eq!(format(f64::NAN).unwrap(), "-1");
+        assert_eq!(format(1e39).unwrap(), "-1");
+    }
+}
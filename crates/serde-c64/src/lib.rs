@@ -1,7 +1,22 @@
+mod binary;
+mod checksum;
+mod de;
 mod error;
+mod float;
+mod loader;
 mod options;
+mod packed;
 mod ser;
+mod trace;
 
+pub use binary::{to_binary_vec, to_binary_writer, BinarySerializer};
+pub use de::{from_reader, from_slice, Deserializer};
 pub use error::{Error, Result};
-pub use options::{ContainerPrefixOptions, Options, SpacingOptions, StringQuotationMethod};
-pub use ser::{to_writer, Serializer};
+pub use float::{MAX_EXACT_INTEGER, MAX_FLOAT, MIN_FLOAT};
+pub use options::{
+    AnnotationMethod, BinaryOptions, ByteEncodingMethod, ChecksumMethod, ContainerPrefixOptions,
+    ContainerTerminationMethod, FloatOverflowMethod, IntegerWidthMethod, LargeIntegerMethod, LineBreakMethod,
+    LineNumberOverflowMethod, LoaderOptions, MemoryBudgetMethod, Options, Sentinel, SpacingOptions,
+    StringQuotationMethod, StringSplitMethod, StringTerminationMethod,
+};
+pub use ser::{merge_into, to_vec, to_writer, Serializer};
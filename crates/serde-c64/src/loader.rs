@@ -0,0 +1,919 @@
+use std::collections::HashMap;
+
+use basic::{BasicDialect, BasicLine, BasicToken, MAX_LINE_LENGTH};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::float::format_f64;
+use crate::options::{
+    ByteEncodingMethod, ChecksumMethod, ContainerTerminationMethod, FloatOverflowMethod, LargeIntegerMethod,
+    LoaderOptions, Options, Sentinel, StringSplitMethod,
+};
+use crate::trace::{trace, EnumVariants, Shape};
+
+/// Two-letter variable names BASIC would read as keywords or reserved
+/// variables.
+const RESERVED_VARIABLE_NAMES: &[&str] = &["DS", "EL", "ER", "FN", "GO", "IF", "ON", "OR", "ST", "TI", "TO"];
+
+/// Keywords used by the generated loader, checksum verifier and unpacker.
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+    "AND", "ASC", "DIM", "FOR", "GOTO", "IF", "INT", "LEN", "MID$", "NEXT", "ON", "OR", "PEEK", "POKE",
+    "PRINT", "READ", "REM", "RESTORE", "RETURN", "THEN", "TO",
+];
+
+/// Width reserved for a line number in a `GOTO` or `THEN` target while laying
+/// out the lines.
+const LINE_NUMBER_WIDTH: usize = 5;
+
+/// Generates BASIC lines that `READ` the data items of `T` into variables.
+pub fn generate_loader<'de, T>(options: &Options, loader_options: &LoaderOptions) -> Result<Vec<BasicLine>>
+where
+    T: Deserialize<'de>,
+{
+    let dialect = options.machine.dialect();
+    generate_listing::<T>(options, loader_options)?
+        .into_iter()
+        .map(|(line_number, text)| {
+            let mut line = BasicLine(line_number, vec![]);
+            for token in tokenize(&text, &dialect) {
+                line.push_token(token, MAX_LINE_LENGTH)?;
+            }
+            Ok(line)
+        })
+        .collect()
+}
+
+/// Generates the loader as numbered lines of plain text.
+fn generate_listing<'de, T>(options: &Options, loader_options: &LoaderOptions) -> Result<Vec<(u16, String)>>
+where
+    T: Deserialize<'de>,
+{
+    loader_options.validate()?;
+
+    if options.checksum_method == ChecksumMethod::PerLine {
+        return Err(Error::InvalidOptions("the loader can't skip per-line checksums"));
+    }
+
+    let (shape, enums) = trace::<T>()?;
+
+    let mut generator = LoaderGenerator {
+        options,
+        loader_options,
+        enums: &enums,
+        statements: vec![],
+        dims: vec![],
+        legend: vec![],
+        variable_count: 0,
+        label_count: 0,
+        loop_variables: vec![],
+        scratch_variables: HashMap::new(),
+    };
+
+    generator.generate(&shape, "", &[])?;
+    generator.layout()
+}
+
+enum Statement {
+    /// Starts a new line that `GOTO` and `THEN` can jump to.
+    Label(usize),
+
+    Code(String),
+
+    /// Adjacent reads are merged into a single `READ` statement.
+    Read(String),
+
+    /// Conditional statement, everything following it on the same line would
+    /// only run when the condition holds.
+    If(String),
+
+    /// Placeholder for the arrays dimensioned after reading the length of an
+    /// outermost sequence.
+    Dim(usize),
+}
+
+#[derive(Clone)]
+struct Loop {
+    index: String,
+    size: String,
+    dim: usize,
+}
+
+struct LoaderGenerator<'a> {
+    options: &'a Options,
+    loader_options: &'a LoaderOptions,
+    enums: &'a HashMap<&'static str, EnumVariants>,
+    statements: Vec<Statement>,
+    dims: Vec<Vec<String>>,
+    legend: Vec<String>,
+    variable_count: usize,
+    label_count: usize,
+    loop_variables: Vec<String>,
+    scratch_variables: HashMap<&'static str, String>,
+}
+
+impl LoaderGenerator<'_> {
+    fn generate(&mut self, shape: &Shape, path: &str, loops: &[Loop]) -> Result<()> {
+        match shape {
+            Shape::Unit => {}
+            Shape::Number => {
+                let variable = self.variable("", path, loops)?;
+                self.read(variable);
+            }
+            Shape::LargeInteger(word_count) => match self.options.large_integer_method {
+                LargeIntegerMethod::Fail => {
+                    let variable = self.variable("", path, loops)?;
+                    self.read(variable);
+                }
+                LargeIntegerMethod::String => {
+                    let variable = self.variable("$", path, loops)?;
+                    self.read(variable);
+                }
+                LargeIntegerMethod::Words => {
+                    for index in 0..*word_count {
+                        let variable = self.variable("", &join_path(path, index), loops)?;
+                        self.read(variable);
+                    }
+                }
+            },
+            Shape::String => self.generate_string(path, loops)?,
+            Shape::Bytes => {
+                if self.options.byte_encoding_method != ByteEncodingMethod::Decimal {
+                    return Err(Error::InvalidOptions(
+                        "the loader can't read packed bytes, use the unpacker",
+                    ));
+                }
+
+                let sequence_length = self.options.container_prefix_options.sequence_length;
+                self.generate_repeated(sequence_length, &[(format!("{}[]", path), &Shape::Number)], path, loops)?;
+            }
+            Shape::Option(inner) => {
+                let tag = self.tag_variable(path, loops)?;
+                self.read(tag.clone());
+
+                let skip = self.label();
+                let condition = self.tag_condition(&tag, 0, "None");
+                self.statements
+                    .push(Statement::If(format!("IF {} THEN {}", condition, jump_target(skip))));
+                self.generate(inner, path, loops)?;
+                self.statements.push(Statement::Label(skip));
+            }
+            Shape::Seq(element) => {
+                let sequence_length = self.options.container_prefix_options.sequence_length;
+                self.generate_repeated(sequence_length, &[(format!("{}[]", path), element)], path, loops)?;
+            }
+            Shape::Map(key, value) => {
+                let map_length = self.options.container_prefix_options.map_length;
+                let elements = [
+                    (format!("{}[].key", path), &**key),
+                    (format!("{}[].value", path), &**value),
+                ];
+                self.generate_repeated(map_length, &elements, path, loops)?;
+            }
+            Shape::Tuple(elements) => {
+                if self.options.container_prefix_options.tuple_length {
+                    let variable = self.scratch_variable("", "skipped tuple length")?;
+                    self.read(variable);
+                }
+
+                for (index, element) in elements.iter().enumerate() {
+                    self.generate(element, &join_path(path, index), loops)?;
+                }
+            }
+            Shape::Struct(fields) => {
+                for (name, field) in fields {
+                    self.generate(field, &join_path(path, name), loops)?;
+                }
+            }
+            Shape::Enum(name) => self.generate_enum(name, path, loops)?,
+        }
+
+        Ok(())
+    }
+
+    fn generate_string(&mut self, path: &str, loops: &[Loop]) -> Result<()> {
+        let variable = self.variable("$", path, loops)?;
+
+        match self.options.string_split_method {
+            StringSplitMethod::Disabled => self.read(variable),
+            StringSplitMethod::Chunked => {
+                let count = self.scratch_variable("", "string chunk count")?;
+                let chunk = self.scratch_variable("$", "string chunk")?;
+                let index = self.loop_variable(loops.len())?;
+
+                self.read(count.clone());
+                self.code(format!("{}=\"\"", variable));
+                self.code(format!("FOR {}=1 TO {}", index, count));
+                self.read(chunk.clone());
+                self.code(format!("{}={}+{}", variable, variable, chunk));
+                self.code("NEXT");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loops over the elements of a sequence or map, either counting to its
+    /// length prefix or until the sentinel comes up.
+    fn generate_repeated(
+        &mut self,
+        length_prefix: bool,
+        elements: &[(String, &Shape)],
+        path: &str,
+        loops: &[Loop],
+    ) -> Result<()> {
+        match self.options.container_termination_method {
+            ContainerTerminationMethod::LengthPrefix if length_prefix => {
+                self.generate_counted_loop(elements, path, loops)
+            }
+            ContainerTerminationMethod::LengthPrefix => Err(unsupported(&format!(
+                "`{}` has no length prefix and",
+                description(path)
+            ))),
+            ContainerTerminationMethod::Sentinel(sentinel) => {
+                self.generate_sentinel_loop(sentinel, elements, path, loops)
+            }
+        }
+    }
+
+    fn generate_counted_loop(&mut self, elements: &[(String, &Shape)], path: &str, loops: &[Loop]) -> Result<()> {
+        let count = self.variable("", &join_path(path, "len"), loops)?;
+        self.read(count.clone());
+
+        let dim = self.dim(loops);
+
+        let end = self.label();
+        self.statements
+            .push(Statement::If(format!("IF {}=0 THEN {}", count, jump_target(end))));
+
+        let index = self.loop_variable(loops.len())?;
+        self.code(format!("FOR {}=1 TO {}", index, count));
+
+        let size = if loops.is_empty() {
+            count
+        } else {
+            self.loader_options.nested_sequence_capacity.to_string()
+        };
+        let mut inner_loops = loops.to_vec();
+        inner_loops.push(Loop { index, size, dim });
+
+        for (element_path, element) in elements {
+            self.generate(element, element_path, &inner_loops)?;
+        }
+
+        self.code("NEXT");
+        self.statements.push(Statement::Label(end));
+        Ok(())
+    }
+
+    /// Reads elements until the first data item of one is the sentinel,
+    /// counting them into the length variable.
+    fn generate_sentinel_loop(
+        &mut self,
+        sentinel: Sentinel,
+        elements: &[(String, &Shape)],
+        path: &str,
+        loops: &[Loop],
+    ) -> Result<()> {
+        let count = self.variable("", &join_path(path, "len"), loops)?;
+        let dim = self.dim(loops);
+        self.code(format!("{}=0", count));
+
+        let start = self.label();
+        let end = self.label();
+        self.statements.push(Statement::Label(start));
+
+        let index = self.loop_variable(loops.len())?;
+        self.code(format!("{}={}+1", index, count));
+
+        let mut inner_loops = loops.to_vec();
+        inner_loops.push(Loop {
+            index: index.clone(),
+            size: self.loader_options.nested_sequence_capacity.to_string(),
+            dim,
+        });
+
+        let outer_statements = std::mem::take(&mut self.statements);
+        for (element_path, element) in elements {
+            self.generate(element, element_path, &inner_loops)?;
+        }
+        let mut element_statements = std::mem::replace(&mut self.statements, outer_statements);
+
+        // The sentinel check has to follow the first `READ` of the element
+        // directly, before any jump or loop
+        let first_read = element_statements
+            .iter()
+            .position(|statement| !matches!(statement, Statement::Code(_)))
+            .filter(|position| matches!(element_statements[*position], Statement::Read(_)))
+            .ok_or_else(|| unsupported(&format!("`{}` with sentinel termination", description(path))))?;
+
+        let Statement::Read(head) = &element_statements[first_read] else {
+            unreachable!();
+        };
+        let sentinel = match sentinel {
+            Sentinel::Number(v) if head.contains('$') => format!("\"{}\"", format_f64(v, FloatOverflowMethod::Fail)?),
+            Sentinel::Number(v) => format_f64(v, FloatOverflowMethod::Fail)?,
+            Sentinel::String(s) if head.contains('$') => format!("\"{}\"", s),
+            Sentinel::String(_) => {
+                return Err(unsupported(&format!(
+                    "`{}` starting with a number and a string sentinel",
+                    description(path)
+                )))
+            }
+        };
+        let check = format!("IF {}={} THEN {}", head, sentinel, jump_target(end));
+        element_statements.insert(first_read + 1, Statement::If(check));
+
+        self.statements.extend(element_statements);
+        self.code(format!("{}={}", count, index));
+        self.code(format!("GOTO {}", jump_target(start)));
+        self.statements.push(Statement::Label(end));
+        Ok(())
+    }
+
+    /// Dimension list the arrays of a sequence go into, starting a new `DIM`
+    /// statement for outermost sequences.
+    fn dim(&mut self, loops: &[Loop]) -> usize {
+        match loops.first() {
+            Some(outer) => outer.dim,
+            None => {
+                self.dims.push(vec![]);
+                self.statements.push(Statement::Dim(self.dims.len() - 1));
+                self.dims.len() - 1
+            }
+        }
+    }
+
+    /// Reads the variant tag and jumps to the code reading the variant's
+    /// contents.
+    fn generate_enum(&mut self, name: &str, path: &str, loops: &[Loop]) -> Result<()> {
+        let enums = self.enums;
+        let variants = &enums[name];
+
+        let tag = self.tag_variable(path, loops)?;
+        self.read(tag.clone());
+
+        if variants.iter().all(|(_, shape)| *shape == Shape::Unit) {
+            return Ok(());
+        }
+
+        let end = self.label();
+        let labels = variants
+            .iter()
+            .map(|(_, shape)| if *shape == Shape::Unit { end } else { self.label() })
+            .collect::<Vec<usize>>();
+        let data_variants = variants
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, shape))| *shape != Shape::Unit)
+            .collect::<Vec<_>>();
+
+        if self.options.emit_enum_names {
+            for (index, (variant, _)) in &data_variants {
+                let condition = self.tag_condition(&tag, *index, variant);
+                self.statements.push(Statement::If(format!(
+                    "IF {} THEN {}",
+                    condition,
+                    jump_target(labels[*index])
+                )));
+            }
+        } else {
+            let last_data_variant = data_variants.last().map_or(0, |(index, _)| *index);
+            let targets = labels[..=last_data_variant].iter().map(|label| jump_target(*label));
+            self.code(format!("ON {}+1 GOTO {}", tag, targets.collect::<Vec<_>>().join(",")));
+        }
+        self.code(format!("GOTO {}", jump_target(end)));
+
+        for (position, (index, (variant, shape))) in data_variants.iter().enumerate() {
+            self.statements.push(Statement::Label(labels[*index]));
+            self.generate(shape, &join_path(path, variant), loops)?;
+            if position + 1 < data_variants.len() {
+                self.code(format!("GOTO {}", jump_target(end)));
+            }
+        }
+
+        self.statements.push(Statement::Label(end));
+        Ok(())
+    }
+
+    fn tag_variable(&mut self, path: &str, loops: &[Loop]) -> Result<String> {
+        let suffix = if self.options.emit_enum_names { "$" } else { "" };
+        self.variable(suffix, &join_path(path, "tag"), loops)
+    }
+
+    fn tag_condition(&self, tag: &str, index: usize, variant: &str) -> String {
+        if self.options.emit_enum_names {
+            format!("{}=\"{}\"", tag, variant)
+        } else {
+            format!("{}={}", tag, index)
+        }
+    }
+
+    /// Allocates a variable for the value at `path`, an array indexed by the
+    /// loop counters when inside a sequence.
+    fn variable(&mut self, suffix: &str, path: &str, loops: &[Loop]) -> Result<String> {
+        let name = format!("{}{}", self.next_variable_name()?, suffix);
+
+        if loops.is_empty() {
+            self.legend.push(format!("{} {}", name, description(path)));
+            Ok(name)
+        } else {
+            let sizes = loops.iter().map(|l| l.size.as_str()).collect::<Vec<_>>().join(",");
+            let indices = loops.iter().map(|l| l.index.as_str()).collect::<Vec<_>>().join(",");
+
+            self.dims[loops[0].dim].push(format!("{}({})", name, sizes));
+            self.legend.push(format!("{}() {}", name, description(path)));
+            Ok(format!("{}({})", name, indices))
+        }
+    }
+
+    fn scratch_variable(&mut self, suffix: &str, description: &'static str) -> Result<String> {
+        if let Some(name) = self.scratch_variables.get(description) {
+            return Ok(name.clone());
+        }
+
+        let name = format!("{}{}", self.next_variable_name()?, suffix);
+        self.legend.push(format!("{} {}", name, description));
+        self.scratch_variables.insert(description, name.clone());
+        Ok(name)
+    }
+
+    fn loop_variable(&mut self, depth: usize) -> Result<String> {
+        while self.loop_variables.len() <= depth {
+            let name = self.next_variable_name()?;
+            self.loop_variables.push(name);
+        }
+        Ok(self.loop_variables[depth].clone())
+    }
+
+    fn next_variable_name(&mut self) -> Result<String> {
+        const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        const SECOND_CHARACTERS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+        loop {
+            let index = self.variable_count;
+            self.variable_count += 1;
+
+            let name = if index < LETTERS.len() {
+                String::from(LETTERS[index] as char)
+            } else {
+                let index = index - LETTERS.len();
+                let first = *LETTERS
+                    .get(index / SECOND_CHARACTERS.len())
+                    .ok_or_else(|| unsupported("a type with this many fields"))?;
+                let second = SECOND_CHARACTERS[index % SECOND_CHARACTERS.len()];
+                format!("{}{}", first as char, second as char)
+            };
+
+            if !RESERVED_VARIABLE_NAMES.contains(&name.as_str()) {
+                return Ok(name);
+            }
+        }
+    }
+
+    fn label(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count - 1
+    }
+
+    fn code(&mut self, text: impl ToString) {
+        self.statements.push(Statement::Code(text.to_string()));
+    }
+
+    fn read(&mut self, variable: String) {
+        self.statements.push(Statement::Read(variable));
+    }
+
+    /// Joins the statements into numbered lines and resolves the jump targets.
+    fn layout(self) -> Result<Vec<(u16, String)>> {
+        let line_length = self.loader_options.line_length;
+        let dialect = self.options.machine.dialect();
+        let mut lines: Vec<Vec<String>> = vec![];
+        let mut current_line: Vec<String> = vec![];
+        let mut label_lines = HashMap::new();
+
+        let fits = |line: &[String], statement: &str| {
+            let separators = line.len();
+            let size = line
+                .iter()
+                .chain([&statement.to_string()])
+                .map(|s| statement_size(s, &dialect))
+                .sum::<usize>();
+            size + separators <= line_length
+        };
+
+        if self.loader_options.emit_variable_legend {
+            for entry in &self.legend {
+                lines.push(vec![format!("REM {}", entry)]);
+            }
+        }
+
+        for statement in &self.statements {
+            let texts = match statement {
+                Statement::Label(id) => {
+                    if !current_line.is_empty() {
+                        lines.push(std::mem::take(&mut current_line));
+                    }
+                    label_lines.insert(*id, lines.len());
+                    continue;
+                }
+                Statement::Read(variable) => {
+                    if let Some((last, rest)) = current_line.split_last() {
+                        let merged = format!("{},{}", last, variable);
+                        if last.starts_with("READ ") && fits(rest, &merged) {
+                            *current_line.last_mut().unwrap() = merged;
+                            continue;
+                        }
+                    }
+                    vec![format!("READ {}", variable)]
+                }
+                Statement::Code(text) | Statement::If(text) => vec![text.clone()],
+                Statement::Dim(id) => {
+                    let mut texts: Vec<String> = vec![];
+                    for array in &self.dims[*id] {
+                        match texts.last_mut() {
+                            Some(last) if fits(&[], &format!("{},{}", last, array)) => {
+                                last.push(',');
+                                last.push_str(array);
+                            }
+                            _ => texts.push(format!("DIM {}", array)),
+                        }
+                    }
+                    texts
+                }
+            };
+
+            for text in texts {
+                if !current_line.is_empty() && !fits(&current_line, &text) {
+                    lines.push(std::mem::take(&mut current_line));
+                }
+                current_line.push(text);
+            }
+
+            if let Statement::If(_) = statement {
+                lines.push(std::mem::take(&mut current_line));
+            }
+        }
+
+        if !current_line.is_empty() {
+            lines.push(current_line);
+        }
+        if label_lines.values().any(|line| *line == lines.len()) {
+            lines.push(vec![String::from("REM")]);
+        }
+
+        let line_numbers = (0..lines.len())
+            .map(|index| {
+                (self.loader_options.line_number_increment as usize)
+                    .checked_mul(index)
+                    .and_then(|offset| offset.checked_add(self.loader_options.line_number_start as usize))
+                    .filter(|line_number| *line_number < self.options.line_number_start as usize)
+                    .map(|line_number| line_number as u16)
+                    .ok_or(Error::InvalidOptions("loader lines overlap the DATA lines"))
+            })
+            .collect::<Result<Vec<u16>>>()?;
+
+        let resolve = |text: &str| {
+            let mut parts = text.split('#');
+            let mut resolved = parts.next().unwrap_or_default().to_string();
+            while let (Some(id), Some(rest)) = (parts.next(), parts.next()) {
+                let line = id
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|id| label_lines.get(&id))
+                    .ok_or_else(|| unsupported("`#` outside of a jump target"))?;
+                resolved.push_str(&line_numbers[*line].to_string());
+                resolved.push_str(rest);
+            }
+            Ok(resolved)
+        };
+
+        lines
+            .iter()
+            .zip(line_numbers.iter())
+            .map(|(statements, line_number)| {
+                let text = statements
+                    .iter()
+                    .map(|s| resolve(s))
+                    .collect::<Result<Vec<_>>>()?
+                    .join(":");
+                Ok((*line_number, text))
+            })
+            .collect()
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::UnsupportedType(format!("{} can't be read by a BASIC loader", what))
+}
+
+/// Placeholder for the line number of a label, resolved once the lines are
+/// laid out.
+fn jump_target(label: usize) -> String {
+    format!("#{}#", label)
+}
+
+fn join_path(path: &str, name: impl ToString) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name.to_string())
+    }
+}
+
+fn description(path: &str) -> String {
+    if path.is_empty() {
+        String::from("value")
+    } else {
+        path.replace('_', "-")
+    }
+}
+
+/// Size of the statement once tokenized, counting jump targets as full line
+/// numbers.
+fn statement_size(text: &str, dialect: &dyn BasicDialect) -> usize {
+    let mut parts = text.split('#');
+    let mut resolved = parts.next().unwrap_or_default().to_string();
+    while let (Some(_), Some(rest)) = (parts.next(), parts.next()) {
+        resolved.push_str(&"0".repeat(LINE_NUMBER_WIDTH));
+        resolved.push_str(rest);
+    }
+    tokenize(&resolved, dialect).iter().map(BasicToken::size).sum()
+}
+
+/// Numbers the lines of a subroutine listing from
+/// `loader_options.line_number_start`, replaces the `#LABEL#` jump targets
+/// with the numbers of the labeled lines and tokenizes the lines.
+///
+/// Fails with `overlap_error` when the lines would reach the `DATA` lines.
+pub(crate) fn assemble_subroutine(
+    options: &Options,
+    loader_options: &LoaderOptions,
+    listing: &[(&str, String)],
+    overlap_error: &'static str,
+) -> Result<Vec<BasicLine>> {
+    let line_number = |index: usize| {
+        loader_options.line_number_start as usize + index * loader_options.line_number_increment as usize
+    };
+
+    if line_number(listing.len() - 1) >= options.line_number_start as usize {
+        return Err(Error::InvalidOptions(overlap_error));
+    }
+
+    let dialect = options.machine.dialect();
+    listing
+        .iter()
+        .enumerate()
+        .map(|(index, (_, text))| {
+            let mut resolved = text.clone();
+            for (label_index, (label, _)) in listing.iter().enumerate().filter(|(_, (label, _))| !label.is_empty()) {
+                resolved = resolved.replace(&format!("#{}#", label), &line_number(label_index).to_string());
+            }
+
+            let mut line = BasicLine(line_number(index) as u16, vec![]);
+            for token in tokenize(&resolved, &dialect) {
+                line.push_token(token, MAX_LINE_LENGTH)?;
+            }
+            Ok(line)
+        })
+        .collect()
+}
+
+/// Tokenizes loader source with the tokens of `dialect`, knowing only the
+/// keywords and operators the loader, the checksum verifier and the unpacker
+/// use.
+pub(crate) fn tokenize(text: &str, dialect: &dyn BasicDialect) -> Vec<BasicToken> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut tokens = vec![];
+    let mut raw = String::new();
+    let mut quoted = false;
+    let mut pos = 0;
+
+    let flush = |raw: &mut String, tokens: &mut Vec<BasicToken>| {
+        if !raw.is_empty() {
+            tokens.push(BasicToken::from(raw.as_str()));
+            raw.clear();
+        }
+    };
+
+    while pos < chars.len() {
+        let c = chars[pos];
+
+        if quoted || c == '"' {
+            quoted ^= c == '"';
+            raw.push(c);
+            pos += 1;
+            continue;
+        }
+
+        let operator = match c {
+            '+' | '-' | '*' | '/' | '<' | '>' | '=' => dialect.keyword_token(&c.to_string()),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            flush(&mut raw, &mut tokens);
+            tokens.push(operator);
+            pos += 1;
+            continue;
+        }
+
+        if c.is_ascii_uppercase() {
+            let mut end = chars[pos..]
+                .iter()
+                .position(|c| !c.is_ascii_uppercase())
+                .map_or(chars.len(), |p| pos + p);
+            let mut word = chars[pos..end].iter().collect::<String>();
+
+            // String functions like `MID$` take the dollar sign along
+            let string_function = format!("{}$", word);
+            if chars.get(end) == Some(&'$') && KEYWORDS.contains(&string_function.as_str()) {
+                word = string_function;
+                end += 1;
+            }
+
+            let keyword = KEYWORDS
+                .contains(&word.as_str())
+                .then(|| dialect.keyword_token(&word))
+                .flatten();
+            match keyword {
+                Some(keyword) => {
+                    flush(&mut raw, &mut tokens);
+                    tokens.push(keyword);
+
+                    if word == "REM" {
+                        raw.extend(&chars[end..]);
+                        break;
+                    }
+                }
+                None => raw.push_str(&word),
+            }
+
+            pos = end;
+            continue;
+        }
+
+        raw.push(c);
+        pos += 1;
+    }
+
+    flush(&mut raw, &mut tokens);
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::error::Error;
+    use crate::loader::generate_listing;
+    use crate::options::{ContainerTerminationMethod, LoaderOptions, Options, Sentinel};
+    use crate::trace::trace;
+
+    #[derive(Deserialize)]
+    #[allow(unused)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rectangle { width: f32, height: f32 },
+    }
+
+    #[derive(Deserialize)]
+    #[allow(unused)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        tags: Option<Vec<Vec<u8>>>,
+    }
+
+    fn listing(options: &Options, loader_options: &LoaderOptions) -> Vec<String> {
+        generate_listing::<Drawing>(options, loader_options)
+            .unwrap()
+            .into_iter()
+            .map(|(line_number, text)| format!("{} {}", line_number, text))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_loader() {
+        let loader_options = LoaderOptions {
+            emit_variable_legend: false,
+            ..LoaderOptions::default()
+        };
+
+        assert_eq!(
+            listing(&Options::default(), &loader_options),
+            [
+                "10 READ A$,B:DIM D(B),E(B),F(B),G(B):IF B=0 THEN 60",
+                "20 FOR C=1 TO B:READ D(C):ON D(C)+1 GOTO 50,30,40:GOTO 50",
+                "30 READ E(C):GOTO 50",
+                "40 READ F(C),G(C)",
+                "50 NEXT",
+                "60 READ H:IF H=0 THEN 110",
+                "70 READ I:DIM J(I),L(I,10):IF I=0 THEN 110",
+                "80 FOR C=1 TO I:READ J(C):IF J(C)=0 THEN 100",
+                "90 FOR K=1 TO J(C):READ L(C,K):NEXT",
+                "100 NEXT",
+                "110 REM",
+            ]
+        );
+
+        let options = Options {
+            emit_enum_names: true,
+            ..Options::default()
+        };
+
+        let listing = listing(&options, &LoaderOptions::default());
+        assert_eq!(
+            &listing[..3],
+            ["10 REM A$ name", "20 REM B shapes.len", "30 REM D$() shapes[].tag"]
+        );
+        assert_eq!(listing[9], "100 REM L() tags[][]");
+        assert_eq!(listing[11], "120 FOR C=1 TO B:READ D$(C):IF D$(C)=\"Circle\" THEN 150");
+
+        let options = Options {
+            line_number_start: 100,
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            generate_listing::<Drawing>(&options, &LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let mut options = Options::default();
+        options.container_prefix_options.sequence_length = false;
+
+        assert!(matches!(
+            generate_listing::<Drawing>(&options, &LoaderOptions::default()),
+            Err(Error::UnsupportedType(_))
+        ));
+
+        options.container_prefix_options.map_length = false;
+        options.container_termination_method = ContainerTerminationMethod::Sentinel(Sentinel::Number(-1.0));
+
+        assert_eq!(
+            generate_listing::<Vec<(String, u8)>>(&options, &loader_options).unwrap(),
+            [
+                (10, String::from("DIM C$(10),D(10):A=0")),
+                (20, String::from("B=A+1:READ C$(B):IF C$(B)=\"-1\" THEN 40")),
+                (30, String::from("READ D(B):A=B:GOTO 20")),
+                (40, String::from("REM")),
+            ]
+        );
+
+        assert!(matches!(
+            generate_listing::<Drawing>(&options, &LoaderOptions::default()),
+            Err(Error::UnsupportedType(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_recursive_types() {
+        #[derive(Deserialize)]
+        #[allow(unused)]
+        struct Node {
+            value: u8,
+            next: Option<Box<Node>>,
+        }
+
+        #[derive(Deserialize)]
+        #[allow(unused)]
+        struct Tree {
+            value: u8,
+            children: Vec<Tree>,
+        }
+
+        assert!(matches!(
+            generate_listing::<Node>(&Options::default(), &LoaderOptions::default()),
+            Err(Error::UnsupportedType(_))
+        ));
+        assert!(matches!(
+            generate_listing::<Tree>(&Options::default(), &LoaderOptions::default()),
+            Err(Error::UnsupportedType(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_nested_enums() {
+        #[derive(Deserialize)]
+        #[allow(unused)]
+        enum Inner {
+            X,
+            Y(u8),
+        }
+
+        #[derive(Deserialize)]
+        #[allow(unused)]
+        enum Outer {
+            A,
+            B(Inner),
+            C,
+        }
+
+        let (_, enum_variants) = trace::<Outer>().unwrap();
+        let names = |name: &str| enum_variants[name].iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(names("Outer"), ["A", "B", "C"]);
+        assert_eq!(names("Inner"), ["X", "Y"]);
+        assert!(generate_listing::<Outer>(&Options::default(), &LoaderOptions::default()).is_ok());
+    }
+}
@@ -1,4 +1,9 @@
-use basic::{PetsciiEncodingOptions, MAX_LINE_LENGTH};
+use basic::{Machine, MemoryConfiguration, PetsciiEncodingOptions, PetsciiString, MAX_LINE_LENGTH, MAX_LINE_NUMBER};
+
+use crate::checksum::CHECKSUM_ITEM_SIZE;
+use crate::de::parse_float;
+use crate::error::{Error, Result};
+use crate::float::format_f64;
 
 #[derive(Debug, Copy, Clone)]
 pub struct ContainerPrefixOptions {
@@ -24,6 +29,52 @@ impl Default for ContainerPrefixOptions {
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
+/// Data item marking the end of a sentinel-terminated sequence or map.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sentinel {
+    /// Matches data items that `READ` reads as the same number.
+    Number(f64),
+
+    /// Matches data items that `READ` reads as the same string, which can't
+    /// contain `"` or `#`.
+    String(&'static str),
+}
+
+impl Sentinel {
+    /// Checks whether a data item, as read by `READ`, is the sentinel.
+    pub(crate) fn matches(&self, text: &str, encoding_options: &PetsciiEncodingOptions) -> bool {
+        match self {
+            Sentinel::Number(v) => parse_float(text) == Some(*v),
+            Sentinel::String(s) => {
+                PetsciiString::from(text).to_petscii(encoding_options)
+                    == PetsciiString::from(*s).to_petscii(encoding_options)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum ContainerTerminationMethod {
+    /// Sequences and maps are only delimited by the length prefixes enabled in
+    /// `ContainerPrefixOptions`.
+    ///
+    /// Example:
+    /// `1000 DATA 3, 10, 20, 30`
+    #[default]
+    LengthPrefix,
+
+    /// Sequences and maps are followed by the sentinel, to be read in a loop
+    /// until it comes up. Requires the sequence and map length prefixes to be
+    /// disabled. Fails with `Error::SentinelCollision` when the first data item
+    /// of an element would read as the sentinel.
+    ///
+    /// Example (`Sentinel(Sentinel::Number(-1.0))`):
+    /// `1000 DATA 10, 20, 30, -1`
+    Sentinel(Sentinel),
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub enum StringQuotationMethod {
     /// Only quotes strings when necessary.
@@ -42,6 +93,171 @@ pub enum StringQuotationMethod {
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum StringSplitMethod {
+    /// Writes every string as a single data item. Strings that don't fit into
+    /// an empty `DATA` line fail with `Error::ItemTooLongForLine`.
+    ///
+    /// Example:
+    /// `1000 DATA HELLO, WORLD`
+    #[default]
+    Disabled,
+
+    /// Writes every string as a chunk count followed by the chunks, splitting
+    /// strings that don't fit into a single `DATA` line.
+    ///
+    /// The chunks can be joined on the BASIC side with:
+    /// `READ N:S$="":FOR I=1 TO N:READ C$:S$=S$+C$:NEXT`
+    ///
+    /// Example (`line_length: 16`):
+    /// `1000 DATA 1, HELLO, 2`, `1001 DATA "LOREM IPSUM "`, `1002 DATA DOLOR`
+    Chunked,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls how byte buffers (`&[u8]`, `serde_bytes`) are written. The packed
+/// methods always write the byte count, so they can't be combined with
+/// `ContainerTerminationMethod::Sentinel`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ByteEncodingMethod {
+    /// Writes every byte as its own data item, like any other sequence.
+    ///
+    /// Example:
+    /// `1000 DATA 3, 1, 2, 255`
+    #[default]
+    Decimal,
+
+    /// Writes the byte count followed by quoted strings of two hexadecimal
+    /// digits per byte, split to fit into the `DATA` lines.
+    ///
+    /// Example:
+    /// `1000 DATA 3, "0102FF"`
+    Hex,
+
+    /// Writes the byte count followed by quoted strings of base 32 digits
+    /// (`0`-`9`, `A`-`V`), five bits per digit, split to fit into the `DATA`
+    /// lines. The last digit is padded with zero bits.
+    ///
+    /// Example:
+    /// `1000 DATA 3, "041FU"`
+    Base32,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum FloatOverflowMethod {
+    /// Fails with `Error::UnrepresentableFloat` on NaN, infinities and values
+    /// outside of ±1.70141183E+38.
+    #[default]
+    Fail,
+
+    /// Clamps infinities and out-of-range values to ±1.70141183E+38. NaN still
+    /// fails with `Error::UnrepresentableFloat`.
+    Clamp,
+
+    /// Writes the given value in place of NaN, infinities and out-of-range
+    /// values.
+    ///
+    /// Example (`Sentinel(-1.0)`):
+    /// `1000 DATA 1.5, -1, 2.5`
+    Sentinel(f64),
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls how 64-bit and 128-bit integers are written, as BASIC only reads
+/// integers up to ±4294967296 exactly.
+///
+/// `String` and `Words` apply to every value of these types regardless of its
+/// magnitude, so the layout seen by `READ` doesn't depend on the data.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum LargeIntegerMethod {
+    /// Writes integers as numbers and fails with `Error::UnrepresentableInteger`
+    /// when they can't be read back exactly.
+    ///
+    /// Example:
+    /// `1000 DATA 1711234567`
+    #[default]
+    Fail,
+
+    /// Writes 64-bit and 128-bit integers as quoted strings.
+    ///
+    /// Example:
+    /// `1000 DATA "1711234567"`
+    String,
+
+    /// Splits 64-bit integers into two and 128-bit integers into four 32-bit
+    /// words, most significant word first. The most significant word of signed
+    /// integers is signed, the rest are unsigned.
+    ///
+    /// Example (`12345678901234`):
+    /// `1000 DATA 2874, 1942892530`
+    Words,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls whether the struct and field the first data item of every `DATA`
+/// line belongs to are named in a `REM`. The data items are the same either
+/// way. Underscores in field names are written as `-`, as PETSCII has none.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AnnotationMethod {
+    /// Writes `DATA` lines only.
+    ///
+    /// Example:
+    /// `1000 DATA 3, JOHN, SMITH, 1970`
+    #[default]
+    Disabled,
+
+    /// Writes a `REM` line in front of every `DATA` line, taking a line number
+    /// of its own.
+    ///
+    /// Example:
+    /// `1000 REM TESTSTRUCT.PEOPLE`, `1001 DATA 3, JOHN, SMITH, 1970`
+    RemLines,
+
+    /// Appends a `REM` statement to the end of every `DATA` line, shortened to
+    /// what still fits into the line.
+    ///
+    /// Example:
+    /// `1000 DATA 3, JOHN, SMITH, 1970:REM TESTSTRUCT.PEOPLE`
+    TrailingRem,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls the checksums written for listings typed in by hand.
+///
+/// A checksum covers the text of a `DATA` line after the `DATA` keyword, up to
+/// the per-line checksum item or the end of the statement. The lines can be
+/// checked on the C64 by the subroutine of `Serializer::emit_verifier`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ChecksumMethod {
+    /// Writes no checksums.
+    #[default]
+    Disabled,
+
+    /// Appends the checksum of every `DATA` line as its last data item.
+    /// `from_slice` checks and skips these items, programs reading the data
+    /// have to skip them too, so the generated loader can't be used.
+    ///
+    /// Example:
+    /// `1000 DATA 3, JOHN, SMITH, 1970, 49695`
+    PerLine,
+
+    /// Writes the checksums of all `DATA` lines after the data, in lines of
+    /// their own that aren't checksummed.
+    ///
+    /// Example:
+    /// `1000 DATA 3, JOHN, SMITH, 1970`, `1001 DATA JANE, DOE, 1982`,
+    /// `1002 DATA BOB, STONE, 1975`, `1003 DATA 49695, 8779, 19356`
+    Table,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
 #[derive(Debug, Copy, Clone)]
 pub struct SpacingOptions {
     /// Put spaces between `DATA` statements and the first data entries.
@@ -70,36 +286,396 @@ impl Default for SpacingOptions {
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
+/// Controls where `DATA` lines end besides running out of `line_length`.
+///
+/// Compound values are sequences, tuples, maps and structs, including tuple
+/// structs and enum variants with contents. The outermost value is at depth 0.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum LineBreakMethod {
+    /// Fills every line up to `line_length`, for the smallest program.
+    ///
+    /// Example:
+    /// `1000 DATA 2, JOHN, SMITH, 1970, JANE, DOE, 1982`
+    Packed,
+
+    /// Ends the line after every compound value.
+    ///
+    /// Example:
+    /// `1000 DATA 2, JOHN, SMITH, 1970`, `1001 DATA JANE, DOE, 1982`
+    #[default]
+    Record,
+
+    /// Starts every element of the outermost sequences and maps on a new line,
+    /// those not nested in another sequence or map.
+    ///
+    /// Example:
+    /// `1000 DATA 2`, `1001 DATA JOHN, SMITH, 1970`, `1002 DATA JANE, DOE, 1982`
+    TopLevelElement,
+
+    /// Ends the line after every compound value at the given depth or above.
+    ///
+    /// Example (`Depth(0)`):
+    /// `1000 DATA 2, JOHN, SMITH, 1970, JANE, DOE, 1982`
+    Depth(usize),
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum LineNumberOverflowMethod {
+    /// Fails with `Error::LineNumberOverflow` when a line number would go past
+    /// `line_number_limit`.
+    #[default]
+    Fail,
+
+    /// Shrinks the line number increment so all lines fit between
+    /// `line_number_start` and `line_number_limit`. Only fails when the lines
+    /// don't fit even with an increment of 1.
+    ///
+    /// Example (`line_number_start: 63990`, `line_number_increment: 10`):
+    /// `63990 DATA ...`, `63993 DATA ...`, `63996 DATA ...`, `63999 DATA ...`
+    Compact,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls whether the size of the generated program is checked against the
+/// memory it is loaded into.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum MemoryBudgetMethod {
+    /// Doesn't check the program size.
+    #[default]
+    Unchecked,
+
+    /// Fails with `Error::ProgramTooLarge` when the program doesn't fit into
+    /// `memory` with `reserved` bytes left over for variables, arrays and
+    /// strings.
+    ///
+    /// Example (`memory: MemoryConfiguration::C64`, `reserved: 1024`):
+    /// the program may take up 37887 bytes.
+    Limited {
+        memory: MemoryConfiguration,
+        reserved: usize,
+    },
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+#[derive(Debug, Copy, Clone)]
+pub struct LoaderOptions {
+    pub line_number_start: u16,
+
+    pub line_number_increment: u16,
+
+    /// Longest loader line, kept shorter than `DATA` lines by default so the
+    /// loader can still be edited on screen.
+    pub line_length: usize,
+
+    /// Size of the array dimensions whose length isn't read before the `DIM`:
+    /// those of nested sequences, and all of them under sentinel termination.
+    /// Longer sequences stop the loader with `?BAD SUBSCRIPT ERROR`.
+    pub nested_sequence_capacity: u16,
+
+    /// Start the loader with `REM` lines naming the value each variable holds.
+    ///
+    /// Example:
+    /// `10 REM A$ name`, `20 REM B shapes.len`, `30 REM D() shapes[].tag`
+    pub emit_variable_legend: bool,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        Self {
+            line_number_start: 10,
+            line_number_increment: 10,
+            line_length: 80,
+            nested_sequence_capacity: 10,
+            emit_variable_legend: true,
+        }
+    }
+}
+
+impl LoaderOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.line_length > MAX_LINE_LENGTH {
+            return Err(Error::InvalidOptions("line_length is larger than MAX_LINE_LENGTH"));
+        }
+
+        if self.line_number_increment == 0 {
+            return Err(Error::InvalidOptions("line_number_increment must not be zero"));
+        }
+
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls the number of bytes integers take up in binary output.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum IntegerWidthMethod {
+    /// Writes integers at the width of their Rust type, `u16` as 2 bytes, etc.
+    #[default]
+    Native,
+
+    /// Writes every integer in the given number of bytes, between 1 and 16.
+    /// Fails with `Error::UnrepresentableInteger` when a value doesn't fit.
+    Fixed(usize),
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Controls how the end of strings is marked in binary output.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum StringTerminationMethod {
+    /// Writes the length of the string in front of it.
+    ///
+    /// Example (`"HI"`):
+    /// `02 48 49`
+    #[default]
+    LengthPrefix,
+
+    /// Writes a zero byte after the string.
+    ///
+    /// Example (`"HI"`):
+    /// `48 49 00`
+    ZeroTerminated,
+
+    /// Sets the high bit of the last character. Fails with
+    /// `Error::UnsupportedType` on empty strings and on characters already
+    /// encoded with the high bit set.
+    ///
+    /// Example (`"HI"`):
+    /// `48 C9`
+    HighBitTerminated,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Options of the binary serializer, writing data for machine language
+/// routines instead of `DATA` lines.
+///
+/// Numbers are little-endian, floats are 5-byte MFLPT as used by the BASIC
+/// ROM and booleans are single bytes. `Option` is written as an enum with the
+/// variants `None` and `Some`.
+#[derive(Debug, Copy, Clone)]
+pub struct BinaryOptions {
+    pub load_address: u16,
+
+    pub encoding_options: PetsciiEncodingOptions,
+
+    pub container_prefix_options: ContainerPrefixOptions,
+
+    pub integer_width_method: IntegerWidthMethod,
+
+    /// Number of bytes of length prefixes and enum variant indices.
+    pub length_width: usize,
+
+    pub string_termination_method: StringTerminationMethod,
+
+    pub float_overflow_method: FloatOverflowMethod,
+}
+
+impl Default for BinaryOptions {
+    fn default() -> Self {
+        Self {
+            load_address: 0xC000,
+            encoding_options: PetsciiEncodingOptions::default(),
+            container_prefix_options: ContainerPrefixOptions::default(),
+            integer_width_method: IntegerWidthMethod::default(),
+            length_width: 1,
+            string_termination_method: StringTerminationMethod::default(),
+            float_overflow_method: FloatOverflowMethod::default(),
+        }
+    }
+}
+
+impl BinaryOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=8).contains(&self.length_width) {
+            return Err(Error::InvalidOptions("length_width must be between 1 and 8"));
+        }
+
+        if let IntegerWidthMethod::Fixed(width) = self.integer_width_method {
+            if !(1..=16).contains(&width) {
+                return Err(Error::InvalidOptions("integer width must be between 1 and 16"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
 #[derive(Debug, Copy, Clone)]
 pub struct Options {
+    /// Machine the program is generated for, which decides its load address.
+    pub machine: Machine,
+
     pub line_length: usize,
 
     pub line_number_start: u16,
 
     pub line_number_increment: u16,
 
+    /// Highest line number the serializer may use, at most `MAX_LINE_NUMBER`.
+    pub line_number_limit: u16,
+
+    pub line_number_overflow: LineNumberOverflowMethod,
+
+    pub memory_budget_method: MemoryBudgetMethod,
+
     pub encoding_options: PetsciiEncodingOptions,
 
     pub container_prefix_options: ContainerPrefixOptions,
 
+    pub container_termination_method: ContainerTerminationMethod,
+
     pub spacing_options: SpacingOptions,
 
+    pub line_break_method: LineBreakMethod,
+
     pub string_quotation_method: StringQuotationMethod,
 
+    pub string_split_method: StringSplitMethod,
+
+    pub byte_encoding_method: ByteEncodingMethod,
+
+    pub float_overflow_method: FloatOverflowMethod,
+
+    pub large_integer_method: LargeIntegerMethod,
+
     pub emit_enum_names: bool,
+
+    pub annotation_method: AnnotationMethod,
+
+    pub checksum_method: ChecksumMethod,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
+            machine: Machine::default(),
             line_length: MAX_LINE_LENGTH,
             line_number_start: 1000,
             line_number_increment: 1,
+            line_number_limit: MAX_LINE_NUMBER,
+            line_number_overflow: LineNumberOverflowMethod::default(),
+            memory_budget_method: MemoryBudgetMethod::default(),
             encoding_options: PetsciiEncodingOptions::default(),
             container_prefix_options: ContainerPrefixOptions::default(),
+            container_termination_method: ContainerTerminationMethod::default(),
             spacing_options: SpacingOptions::default(),
+            line_break_method: LineBreakMethod::default(),
             string_quotation_method: StringQuotationMethod::default(),
+            string_split_method: StringSplitMethod::default(),
+            byte_encoding_method: ByteEncodingMethod::default(),
+            float_overflow_method: FloatOverflowMethod::default(),
+            large_integer_method: LargeIntegerMethod::default(),
             emit_enum_names: false,
+            annotation_method: AnnotationMethod::default(),
+            checksum_method: ChecksumMethod::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Default options for `machine`, using its line length limit and failing
+    /// with `Error::ProgramTooLarge` when the program doesn't fit into its
+    /// BASIC memory.
+    ///
+    /// The character set isn't machine-specific, as business keyboard PETs
+    /// start up in the shifted one and the other machines in the unshifted
+    /// one. Set `encoding_options` for the character set the program runs in.
+    pub fn for_machine(machine: Machine) -> Self {
+        Self {
+            machine,
+            line_length: machine.max_line_length(),
+            memory_budget_method: MemoryBudgetMethod::Limited {
+                memory: machine.memory(),
+                reserved: 0,
+            },
+            ..Self::default()
         }
     }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.line_length > MAX_LINE_LENGTH {
+            return Err(Error::InvalidOptions("line_length is larger than MAX_LINE_LENGTH"));
+        }
+
+        if self.line_length > self.machine.max_line_length() {
+            return Err(Error::InvalidOptions(
+                "line_length is larger than the line length limit of the machine",
+            ));
+        }
+
+        if self.line_number_limit > MAX_LINE_NUMBER {
+            return Err(Error::InvalidOptions(
+                "line_number_limit is larger than MAX_LINE_NUMBER",
+            ));
+        }
+
+        if self.line_number_start > self.line_number_limit {
+            return Err(Error::InvalidOptions(
+                "line_number_start is larger than line_number_limit",
+            ));
+        }
+
+        if self.line_number_increment == 0 {
+            return Err(Error::InvalidOptions("line_number_increment must not be zero"));
+        }
+
+        if let ContainerTerminationMethod::Sentinel(sentinel) = self.container_termination_method {
+            if self.container_prefix_options.sequence_length || self.container_prefix_options.map_length {
+                return Err(Error::InvalidOptions(
+                    "sentinel termination requires sequence and map length prefixes to be disabled",
+                ));
+            }
+
+            match sentinel {
+                Sentinel::Number(v) if format_f64(v, FloatOverflowMethod::Fail).is_err() => {
+                    return Err(Error::InvalidOptions("sentinel can't be represented in BASIC"));
+                }
+                // Quotes end the data item and `#` marks jump targets in the loader
+                Sentinel::String(s) if s.contains(['"', '#']) => {
+                    return Err(Error::InvalidOptions("sentinel string can't contain `\"` or `#`"));
+                }
+                _ => {}
+            }
+
+            if self.byte_encoding_method != ByteEncodingMethod::Decimal {
+                return Err(Error::InvalidOptions(
+                    "packed bytes are prefixed with their length and can't be sentinel-terminated",
+                ));
+            }
+        }
+
+        if self.string_split_method == StringSplitMethod::Chunked && self.max_string_chunk_length() == 0 {
+            return Err(Error::InvalidOptions("line_length is too short for splitting strings"));
+        }
+
+        if self.byte_encoding_method != ByteEncodingMethod::Decimal && self.max_string_chunk_length() == 0 {
+            return Err(Error::InvalidOptions("line_length is too short for packing bytes"));
+        }
+
+        Ok(())
+    }
+
+    /// Longest string chunk that still fits into an empty `DATA` line when
+    /// quoted, leaving room for a per-line checksum.
+    pub(crate) fn max_string_chunk_length(&self) -> usize {
+        let data_statement_length = if self.spacing_options.space_after_data_statement {
+            2
+        } else {
+            1
+        };
+        let checksum_length = match self.checksum_method {
+            ChecksumMethod::PerLine => CHECKSUM_ITEM_SIZE,
+            _ => 0,
+        };
+        self.line_length
+            .saturating_sub(data_statement_length + 2 + checksum_length)
+    }
 }
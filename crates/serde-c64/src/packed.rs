@@ -0,0 +1,194 @@
+use basic::{BasicLine, PetsciiVariant};
+
+use crate::error::{Error, Result};
+use crate::loader::assemble_subroutine;
+use crate::options::{ByteEncodingMethod, LoaderOptions, Options};
+
+/// Number of bits a digit of the packed byte encodings holds, `None` for
+/// bytes written as numbers.
+pub fn bits_per_digit(method: ByteEncodingMethod) -> Option<u32> {
+    match method {
+        ByteEncodingMethod::Decimal => None,
+        ByteEncodingMethod::Hex => Some(4),
+        ByteEncodingMethod::Base32 => Some(5),
+    }
+}
+
+/// Number of digits `len` bytes are packed into.
+pub fn packed_length(len: usize, bits: u32) -> usize {
+    (len * 8).div_ceil(bits as usize)
+}
+
+/// Packs bytes into digits of `bits` bits, most significant bit first.
+///
+/// The letter digits are written in the case that encodes to the PETSCII
+/// codes of unshifted letters, so `ASC` reads the same values in both
+/// character sets.
+pub fn pack_bytes(bytes: &[u8], bits: u32, variant: PetsciiVariant) -> String {
+    let mut packed = String::with_capacity(packed_length(bytes.len(), bits));
+    let mut buffer = 0u32;
+    let mut buffer_bits = 0;
+
+    let mut push_digit = |digit: u32| {
+        let c = char::from_digit(digit, 1 << bits).unwrap();
+        packed.push(match variant {
+            PetsciiVariant::Unshifted => c.to_ascii_uppercase(),
+            PetsciiVariant::Shifted => c,
+        });
+    };
+
+    for &b in bytes {
+        buffer = (buffer << 8) | b as u32;
+        buffer_bits += 8;
+        while buffer_bits >= bits {
+            buffer_bits -= bits;
+            push_digit((buffer >> buffer_bits) & ((1 << bits) - 1));
+        }
+    }
+
+    if buffer_bits > 0 {
+        push_digit((buffer << (bits - buffer_bits)) & ((1 << bits) - 1));
+    }
+
+    packed
+}
+
+/// Unpacks the first `len` bytes of digits of `bits` bits. Returns `None` on
+/// invalid digits or when there are not enough of them.
+pub fn unpack_bytes(packed: &str, len: usize, bits: u32) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(len);
+    let mut buffer = 0u32;
+    let mut buffer_bits = 0;
+
+    for c in packed.chars() {
+        if bytes.len() == len {
+            break;
+        }
+
+        buffer = (buffer << bits) | c.to_digit(1 << bits)?;
+        buffer_bits += bits;
+        if buffer_bits >= 8 {
+            buffer_bits -= 8;
+            bytes.push((buffer >> buffer_bits) as u8);
+            buffer &= (1 << buffer_bits) - 1;
+        }
+    }
+
+    (bytes.len() == len).then_some(bytes)
+}
+
+/// Generates a BASIC subroutine that `READ`s the next packed byte buffer and
+/// `POKE`s it into memory from the address in `ZA`.
+///
+/// The first line jumps over the subroutine, so running into it does no
+/// harm. It returns with `ZA` pointing past the last byte.
+pub fn generate_unpacker(options: &Options, loader_options: &LoaderOptions) -> Result<Vec<BasicLine>> {
+    loader_options.validate()?;
+
+    let base = match bits_per_digit(options.byte_encoding_method) {
+        Some(bits) => 1 << bits,
+        None => {
+            return Err(Error::InvalidOptions(
+                "the unpacker needs a packed byte_encoding_method",
+            ))
+        }
+    };
+
+    // `ZP` is the power of two past the highest bit in `ZD` waiting to be
+    // written, kept exact by only multiplying and dividing by powers of two.
+    let listing: Vec<(&str, String)> = vec![
+        ("", String::from("GOTO #END#")),
+        ("", String::from("READ ZN:ZD=0:ZP=1:IF ZN=0 THEN #DONE#")),
+        ("STRING", String::from("READ ZX$:ZI=1")),
+        ("DIGIT", String::from("ZV=ASC(MID$(ZX$,ZI,1))-48:IF ZV>9 THEN ZV=ZV-7")),
+        ("", format!("ZD=ZD*{0}+ZV:ZP=ZP*{0}:IF ZP<256 THEN #NEXT#", base)),
+        (
+            "",
+            String::from("ZP=ZP/256:ZB=INT(ZD/ZP):ZD=ZD-ZB*ZP:POKE ZA,ZB:ZA=ZA+1:ZN=ZN-1:IF ZN=0 THEN #DONE#"),
+        ),
+        ("NEXT", String::from("ZI=ZI+1:IF ZI>LEN(ZX$) THEN #STRING#")),
+        ("", String::from("GOTO #DIGIT#")),
+        ("DONE", String::from("RETURN")),
+        ("END", String::from("REM")),
+    ];
+
+    assemble_subroutine(
+        options,
+        loader_options,
+        &listing,
+        "unpacker lines overlap the DATA lines",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use basic::{BasicDialect, BasicToken, KeywordTable, PetsciiEncodingOptions, PetsciiVariant};
+
+    use crate::error::Error;
+    use crate::options::{ByteEncodingMethod, LoaderOptions, Options};
+    use crate::packed::{generate_unpacker, pack_bytes, unpack_bytes};
+
+    #[test]
+    pub fn test_pack_bytes() {
+        assert_eq!(pack_bytes(&[], 4, PetsciiVariant::Unshifted), "");
+        assert_eq!(pack_bytes(&[1, 2, 255], 4, PetsciiVariant::Unshifted), "0102FF");
+        assert_eq!(pack_bytes(&[1, 2, 255], 4, PetsciiVariant::Shifted), "0102ff");
+        assert_eq!(pack_bytes(&[1, 2, 255], 5, PetsciiVariant::Unshifted), "041FU");
+        assert_eq!(pack_bytes(&[0xFF; 5], 5, PetsciiVariant::Unshifted), "VVVVVVVV");
+
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        for bits in [4, 5] {
+            for len in [0, 1, 2, 3, 4, 5, 6, 7, 8, 255] {
+                let packed = pack_bytes(&bytes[..len], bits, PetsciiVariant::Unshifted);
+                assert_eq!(unpack_bytes(&packed, len, bits).as_deref(), Some(&bytes[..len]));
+                assert_eq!(
+                    unpack_bytes(&packed.to_lowercase(), len, bits).as_deref(),
+                    Some(&bytes[..len])
+                );
+            }
+        }
+
+        assert_eq!(unpack_bytes("0102", 3, 4), None);
+        assert_eq!(unpack_bytes("01G2", 2, 4), None);
+        assert_eq!(unpack_bytes("041FU", 3, 5), Some(vec![1, 2, 255]));
+    }
+
+    #[test]
+    pub fn test_generate_unpacker() {
+        let options = Options {
+            byte_encoding_method: ByteEncodingMethod::Base32,
+            ..Options::default()
+        };
+        let lines = generate_unpacker(&options, &LoaderOptions::default()).unwrap();
+        let text = |index: usize| {
+            lines[index]
+                .1
+                .iter()
+                .map(|token| match token {
+                    BasicToken::Raw(_) => {
+                        String::from_utf8(token.to_bytes(&PetsciiEncodingOptions::default())).unwrap()
+                    }
+                    BasicToken::Keyword(_) | BasicToken::Bytes(_) => {
+                        let bytes = token.to_bytes(&PetsciiEncodingOptions::default());
+                        format!("{{{}}}", KeywordTable::default().keyword_name(&bytes).unwrap().0)
+                    }
+                })
+                .collect::<String>()
+        };
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(text(0), "{GOTO} 100");
+        assert_eq!(
+            text(3),
+            "ZV{=}{ASC}({MID$}(ZX$,ZI,1)){-}48:\
+             {IF} ZV{>}9 {THEN} ZV{=}ZV{-}7"
+        );
+        assert!(text(4).ends_with("{THEN} 70"));
+        assert_eq!(text(9), "{REM}");
+
+        assert!(matches!(
+            generate_unpacker(&Options::default(), &LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+}
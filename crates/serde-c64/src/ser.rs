@@ -1,57 +1,594 @@
 use std::io::Write;
 
-use basic::{BasicKeyword, BasicLine, BasicProgram, BasicToken, PetsciiString};
-use serde::{ser, Serialize};
+use basic::{BasicDialect, BasicLine, BasicProgram, BasicProgramWriter, BasicToken, PetsciiString, MAX_STRING_LENGTH};
+use serde::{ser, Deserialize, Serialize};
 
+use crate::checksum::{generate_verifier, line_checksum, CHECKSUM_ITEM_SIZE};
 use crate::error::{Error, Result};
-use crate::options::Options;
-use crate::StringQuotationMethod;
-
-pub struct Serializer {
+use crate::float::{format_f32, format_f64, MAX_EXACT_INTEGER};
+use crate::loader::generate_loader;
+use crate::options::{
+    AnnotationMethod, ByteEncodingMethod, ChecksumMethod, ContainerTerminationMethod, FloatOverflowMethod,
+    LargeIntegerMethod, LineBreakMethod, LineNumberOverflowMethod, LoaderOptions, MemoryBudgetMethod, Options,
+    Sentinel,
+};
+use crate::packed::{bits_per_digit, generate_unpacker, pack_bytes};
+use crate::{StringQuotationMethod, StringSplitMethod};
+
+pub struct Serializer<'w> {
     options: Options,
     basic_program: BasicProgram,
+    basic_program_writer: Option<BasicProgramWriter<Box<dyn Write + 'w>>>,
+    basic_prefix_written: bool,
+    basic_program_size: usize,
+    basic_loader_lines: Vec<BasicLine>,
     basic_next_line: BasicLine,
     basic_next_line_number: u16,
     basic_next_line_started: bool,
+    basic_next_line_annotation: Option<String>,
+    basic_verifier_options: Option<LoaderOptions>,
+    basic_unpacker_options: Option<LoaderOptions>,
+    data_line_checksums: Vec<u32>,
+    data_line_count: usize,
+    data_item_count: usize,
+    writing_checksum_table: bool,
+    struct_path: Vec<StructPathSegment>,
+    containers: Vec<Container>,
+    depth: usize,
+}
+
+/// Sequence or map being serialized, used to check its length prefix or to
+/// write one once the elements are counted.
+struct Container {
+    expected_len: Option<usize>,
+    len: usize,
+    buffer: Option<Vec<BufferedEvent>>,
+    awaiting_first_item: bool,
+}
+
+/// Output held back until the length of the enclosing container is known.
+enum BufferedEvent {
+    Item { item: String, annotation: Option<String> },
+    LineBreak,
+}
+
+/// Struct being serialized and its field being written, used to annotate the
+/// `DATA` lines.
+struct StructPathSegment {
+    name: &'static str,
+    field: Option<String>,
+    position: usize,
 }
 
+/// Serializes `value` line by line into `writer`, without holding the whole
+/// program in memory. The writer may have received part of the program when
+/// this fails.
+///
+/// Compacting line numbers needs every line at once, so with
+/// `LineNumberOverflowMethod::Compact` the program is built in memory first.
 pub fn to_writer<W, T>(mut writer: W, value: &T, options: Options) -> Result<()>
 where
     W: Write,
     T: ?Sized + Serialize,
 {
-    let mut serializer = Serializer {
-        options,
-        basic_program: BasicProgram {
-            load_address: 0x0801,
-            encoding_options: options.encoding_options,
-            contents: vec![],
-        },
-        basic_next_line: BasicLine(options.line_number_start, vec![BasicKeyword::Data.into()]),
-        basic_next_line_number: options.line_number_start,
-        basic_next_line_started: false,
-    };
+    if options.line_number_overflow == LineNumberOverflowMethod::Compact {
+        writer.write_all(&to_vec(value, options)?)?;
+        return Ok(());
+    }
+
+    let mut serializer = Serializer::with_writer(writer, options)?;
+    value.serialize(&mut serializer)?;
+    serializer.finish_writer()
+}
+
+pub fn to_vec<T>(value: &T, options: Options) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(options)?;
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_program()?.to_bytes())
+}
 
+/// Serializes `value` into the `DATA` block of an existing program.
+///
+/// The lines from `line_number_start` to `line_number_limit` are replaced with
+/// the new `DATA` lines, which fails if any of them contains code. The memory
+/// budget covers the whole merged program, and the program is left as it was
+/// when it doesn't fit.
+pub fn merge_into<T>(basic_program: &mut BasicProgram, value: &T, options: Options) -> Result<()>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(options)?;
     value.serialize(&mut serializer)?;
-    serializer.finalize_line()?;
 
-    writer.write_all(&serializer.basic_program.to_bytes()).unwrap();
+    let data_lines = serializer
+        .into_program()?
+        .contents
+        .into_iter()
+        .map(|BasicLine(line_number, tokens)| {
+            let line_bin = tokens
+                .iter()
+                .flat_map(|t| t.to_bytes(&options.encoding_options))
+                .collect();
+            BasicLine(line_number, vec![BasicToken::Bytes(line_bin)])
+        })
+        .collect();
+
+    let previous_contents = basic_program.contents.clone();
+    basic_program.replace_lines(options.line_number_start..=options.line_number_limit, data_lines)?;
+
+    if let Err(err) = check_memory_budget(&options, basic_program.load_address, basic_program.size()) {
+        basic_program.contents = previous_contents;
+        return Err(err);
+    }
+    Ok(())
+}
 
+/// Checks a program of `size` bytes loaded at `load_address` against
+/// `options.memory_budget_method`. A program loaded below the start of the
+/// memory configuration has no room at all.
+fn check_memory_budget(options: &Options, load_address: u16, size: usize) -> Result<()> {
+    if let MemoryBudgetMethod::Limited { memory, reserved } = options.memory_budget_method {
+        let available = memory
+            .available_from(load_address)
+            .unwrap_or(0)
+            .saturating_sub(reserved);
+        if size > available {
+            return Err(Error::ProgramTooLarge { size, available });
+        }
+    }
     Ok(())
 }
 
-impl Serializer {
+/// Token of `DATA` or `REM` in the dialect of a machine.
+fn keyword_token(dialect: &dyn BasicDialect, name: &str) -> BasicToken {
+    dialect
+        .keyword_token(name)
+        .expect("the BASIC of every machine has DATA and REM")
+}
+
+impl<'w> Serializer<'w> {
+    /// Creates a serializer for an empty program loaded at the load address
+    /// of `options.machine`.
+    ///
+    /// Several values can be serialized into the same serializer one after
+    /// another, their data items follow each other in the `DATA` lines.
+    pub fn new(options: Options) -> Result<Self> {
+        options.validate()?;
+
+        let basic_program = BasicProgram::new(options.machine.load_address(), options.encoding_options, vec![])
+            .with_dialect(options.machine.dialect());
+        let data_token = keyword_token(basic_program.dialect(), "DATA");
+
+        Ok(Self {
+            options,
+            basic_program,
+            basic_program_writer: None,
+            basic_prefix_written: false,
+            basic_program_size: 2,
+            basic_loader_lines: vec![],
+            basic_next_line: BasicLine(options.line_number_start, vec![data_token]),
+            basic_next_line_number: options.line_number_start,
+            basic_next_line_started: false,
+            basic_next_line_annotation: None,
+            basic_verifier_options: None,
+            basic_unpacker_options: None,
+            data_line_checksums: vec![],
+            data_line_count: 0,
+            data_item_count: 0,
+            writing_checksum_table: false,
+            struct_path: vec![],
+            containers: vec![],
+            depth: 0,
+        })
+    }
+
+    /// Creates a serializer that writes every finished line straight to
+    /// `writer` instead of collecting the program. Finish it with
+    /// `finish_writer`.
+    ///
+    /// The loader and unpacker are written in front of the first `DATA` line,
+    /// so they have to be emitted before serializing. Neither the checksum
+    /// verifier nor `LineNumberOverflowMethod::Compact` work this way, as both
+    /// need every `DATA` line before the first one is written.
+    pub fn with_writer(writer: impl Write + 'w, options: Options) -> Result<Self> {
+        if options.line_number_overflow == LineNumberOverflowMethod::Compact {
+            return Err(Error::InvalidOptions("compacting line numbers needs the whole program"));
+        }
+
+        let mut serializer = Self::new(options)?;
+
+        let writer: Box<dyn Write + 'w> = Box::new(writer);
+        serializer.basic_program_writer = Some(BasicProgramWriter::new(
+            writer,
+            serializer.basic_program.load_address,
+            options.encoding_options,
+        )?);
+        Ok(serializer)
+    }
+
+    /// Generates a BASIC loader that `READ`s the data items of `T` back into
+    /// variables, and puts it in front of the `DATA` lines.
+    ///
+    /// The loader lines are numbered from `loader_options.line_number_start`
+    /// and must end before `line_number_start` of the serializer options.
+    /// Calling it again replaces the previous loader.
+    pub fn emit_loader<'de, T>(&mut self, loader_options: LoaderOptions) -> Result<()>
+    where
+        T: Deserialize<'de>,
+    {
+        self.check_prefix_not_written()?;
+        self.basic_loader_lines = generate_loader::<T>(&self.options, &loader_options)?;
+        Ok(())
+    }
+
+    /// Generates a BASIC subroutine checking the `DATA` lines against their
+    /// checksums, and puts it in front of the `DATA` lines. Requires a
+    /// `checksum_method` other than `Disabled`.
+    ///
+    /// The subroutine walks the program in memory and returns with `ZE` set to
+    /// the number of the first bad line, or 0, leaving the data `RESTORE`d.
+    /// Its first line jumps over it, the returned line number is the one to
+    /// `GOSUB`. The lines must not overlap with those of `emit_loader`.
+    pub fn emit_verifier(&mut self, loader_options: LoaderOptions) -> Result<u16> {
+        if self.options.checksum_method == ChecksumMethod::Disabled {
+            return Err(Error::InvalidOptions("the verifier needs a checksum_method"));
+        }
+
+        if self.basic_program_writer.is_some() {
+            return Err(Error::InvalidOptions(
+                "the verifier can't be written in front of streamed lines",
+            ));
+        }
+
+        loader_options.validate()?;
+        self.basic_verifier_options = Some(loader_options);
+        Ok(loader_options
+            .line_number_start
+            .saturating_add(loader_options.line_number_increment))
+    }
+
+    /// Generates a BASIC subroutine that `READ`s the next packed byte buffer
+    /// and `POKE`s it into memory, and puts it in front of the `DATA` lines.
+    /// Requires a `byte_encoding_method` other than `Decimal`.
+    ///
+    /// The bytes are written from the address in `ZA`, which the subroutine
+    /// leaves pointing past the last byte. Its first line jumps over it, the
+    /// returned line number is the one to `GOSUB`. The lines must not overlap
+    /// with those of `emit_loader` and `emit_verifier`.
+    pub fn emit_unpacker(&mut self, loader_options: LoaderOptions) -> Result<u16> {
+        if self.options.byte_encoding_method == ByteEncodingMethod::Decimal {
+            return Err(Error::InvalidOptions(
+                "the unpacker needs a packed byte_encoding_method",
+            ));
+        }
+
+        self.check_prefix_not_written()?;
+
+        loader_options.validate()?;
+        self.basic_unpacker_options = Some(loader_options);
+        Ok(loader_options
+            .line_number_start
+            .saturating_add(loader_options.line_number_increment))
+    }
+
+    /// Finishes the last `DATA` line and returns the generated program.
+    pub fn into_program(mut self) -> Result<BasicProgram> {
+        if self.basic_program_writer.is_some() {
+            return Err(Error::InvalidOptions("the program was written to the writer"));
+        }
+
+        self.finish()?;
+
+        let lines = self.subroutine_lines()?;
+        self.basic_program.contents.splice(0..0, lines);
+        self.check_memory_budget(self.basic_program.size())?;
+        Ok(self.basic_program)
+    }
+
+    /// Finishes the last `DATA` line and ends the program written by a
+    /// serializer created with `with_writer`.
+    pub fn finish_writer(mut self) -> Result<()> {
+        if self.basic_program_writer.is_none() {
+            return Err(Error::InvalidOptions("the serializer has no writer"));
+        }
+
+        self.finish()?;
+        self.write_prefix_lines()?;
+
+        if let Some(writer) = self.basic_program_writer.take() {
+            writer.finish()?.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Takes the loader lines and generates the subroutines going in front of
+    /// the `DATA` lines, sorted by line number.
+    fn subroutine_lines(&mut self) -> Result<Vec<BasicLine>> {
+        let mut subroutines = vec![];
+        if let Some(verifier_options) = self.basic_verifier_options {
+            subroutines.push(generate_verifier(
+                &self.options,
+                &verifier_options,
+                self.data_line_count,
+                self.data_item_count,
+            )?);
+        }
+        if let Some(unpacker_options) = self.basic_unpacker_options {
+            subroutines.push(generate_unpacker(&self.options, &unpacker_options)?);
+        }
+
+        let mut ranges = vec![];
+        let mut lines = std::mem::take(&mut self.basic_loader_lines);
+        if let (Some(first), Some(last)) = (lines.first(), lines.last()) {
+            ranges.push((first.0, last.0));
+        }
+
+        for subroutine_lines in subroutines {
+            let first = subroutine_lines[0].0;
+            let last = subroutine_lines[subroutine_lines.len() - 1].0;
+            if ranges
+                .iter()
+                .any(|(other_first, other_last)| first <= *other_last && *other_first <= last)
+            {
+                return Err(Error::InvalidOptions(
+                    "subroutine lines overlap the loader or another subroutine",
+                ));
+            }
+
+            ranges.push((first, last));
+            lines.extend(subroutine_lines);
+        }
+        lines.sort_by_key(|line| line.0);
+
+        Ok(lines)
+    }
+
+    /// Writes the loader and the subroutines once, before the first streamed
+    /// `DATA` line.
+    fn write_prefix_lines(&mut self) -> Result<()> {
+        if self.basic_program_writer.is_none() || self.basic_prefix_written {
+            return Ok(());
+        }
+
+        self.basic_prefix_written = true;
+        let lines = self.subroutine_lines()?;
+        self.basic_program_size += lines.iter().map(BasicLine::memory_size).sum::<usize>();
+        self.check_memory_budget(self.basic_program_size)?;
+
+        if let Some(writer) = &mut self.basic_program_writer {
+            for line in &lines {
+                writer.write_line(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails once the program grows past the memory left by
+    /// `memory_budget_method`.
+    fn check_memory_budget(&self, size: usize) -> Result<()> {
+        check_memory_budget(&self.options, self.basic_program.load_address, size)
+    }
+
+    fn check_prefix_not_written(&self) -> Result<()> {
+        if self.basic_prefix_written {
+            return Err(Error::InvalidOptions(
+                "the first line was already written to the writer",
+            ));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.finalize_line()?;
+
+        if self.options.checksum_method == ChecksumMethod::Table {
+            self.writing_checksum_table = true;
+            for checksum in std::mem::take(&mut self.data_line_checksums) {
+                self.push_data_item(checksum.to_string(), None)?;
+            }
+            self.finalize_line()?;
+        }
+
+        self.compact_line_numbers();
+        Ok(())
+    }
+
     fn finalize_line(&mut self) -> Result<()> {
+        if let Some(buffer) = self.active_buffer() {
+            buffer.push(BufferedEvent::LineBreak);
+            return Ok(());
+        }
+
         if self.basic_next_line_started {
-            self.basic_program.contents.push(self.basic_next_line.clone());
+            let mut tokens = std::mem::take(&mut self.basic_next_line.1);
+
+            if !self.writing_checksum_table {
+                let line_bin: Vec<u8> = tokens[1..]
+                    .iter()
+                    .flat_map(|t| t.to_bytes(&self.options.encoding_options))
+                    .collect();
+                let checksum = line_checksum(&line_bin);
+
+                self.data_line_count += 1;
+                self.data_item_count += tokens.len() - 1;
+
+                match self.options.checksum_method {
+                    ChecksumMethod::Disabled => {}
+                    ChecksumMethod::PerLine => tokens.push(self.format_basic_data_item(checksum.to_string())),
+                    ChecksumMethod::Table => self.data_line_checksums.push(checksum),
+                }
+            }
+
+            if let Some(annotation) = self.basic_next_line_annotation.take() {
+                let remark = vec![
+                    keyword_token(self.basic_program.dialect(), "REM"),
+                    format!(" {}", annotation).as_str().into(),
+                ];
+
+                match self.options.annotation_method {
+                    AnnotationMethod::Disabled => {}
+                    AnnotationMethod::RemLines => self.push_line(remark)?,
+                    AnnotationMethod::TrailingRem => {
+                        tokens.push(":".into());
+                        tokens.extend(remark);
+                    }
+                }
+            }
 
-            self.basic_next_line_number += self.options.line_number_increment; // TODO: Overflow
-            self.basic_next_line = BasicLine(self.basic_next_line_number, vec![BasicKeyword::Data.into()]);
+            self.push_line(tokens)?;
+
+            self.basic_next_line = BasicLine(
+                self.basic_next_line_number,
+                vec![keyword_token(self.basic_program.dialect(), "DATA")],
+            );
             self.basic_next_line_started = false;
         }
         Ok(())
     }
 
+    fn push_line(&mut self, tokens: Vec<BasicToken>) -> Result<()> {
+        if self.basic_next_line_number > self.options.line_number_limit {
+            let available_line_count = (self.options.line_number_limit - self.options.line_number_start) as usize;
+
+            match self.options.line_number_overflow {
+                LineNumberOverflowMethod::Fail => return Err(Error::LineNumberOverflow),
+                LineNumberOverflowMethod::Compact if self.basic_program.contents.len() > available_line_count => {
+                    return Err(Error::LineNumberOverflow)
+                }
+                LineNumberOverflowMethod::Compact => {}
+            }
+        }
+
+        let line = BasicLine(self.basic_next_line_number, tokens);
+        self.write_prefix_lines()?;
+
+        self.basic_program_size += line.memory_size();
+        self.check_memory_budget(self.basic_program_size)?;
+
+        match &mut self.basic_program_writer {
+            Some(writer) => writer.write_line(&line)?,
+            None => self.basic_program.contents.push(line),
+        }
+
+        self.basic_next_line_number = self
+            .basic_next_line_number
+            .saturating_add(self.options.line_number_increment);
+        Ok(())
+    }
+
+    /// Names the struct fields the next data item belongs to.
+    fn struct_path_annotation(&self) -> Option<String> {
+        if self.options.annotation_method == AnnotationMethod::Disabled || self.struct_path.is_empty() {
+            return None;
+        }
+
+        let annotation = self
+            .struct_path
+            .iter()
+            .map(|segment| match &segment.field {
+                Some(field) => format!("{}.{}", segment.name, field),
+                None => segment.name.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join("/")
+            .replace('_', "-");
+
+        Some(annotation)
+    }
+
+    /// Keeps the annotation of the line being started, shortened to the space
+    /// left for the `REM`.
+    fn annotate_line(&mut self, annotation: Option<String>) {
+        let Some(annotation) = annotation else {
+            return;
+        };
+
+        // `REM` token and a space, preceded by a colon when trailing
+        let available_length = match self.options.annotation_method {
+            AnnotationMethod::TrailingRem => self.options.line_length.saturating_sub(self.basic_next_line.size() + 3),
+            _ => self.options.line_length.saturating_sub(2),
+        };
+
+        if available_length > 0 {
+            self.basic_next_line_annotation = Some(annotation.chars().take(available_length).collect());
+        }
+    }
+
+    /// Line length left for data items, after the trailing `REM` reserved
+    /// when the line was started and the per-line checksum.
+    fn data_line_length(&self) -> usize {
+        let line_length = match (&self.basic_next_line_annotation, self.options.annotation_method) {
+            (Some(annotation), AnnotationMethod::TrailingRem) => {
+                self.options.line_length - annotation.chars().count() - 3
+            }
+            _ => self.options.line_length,
+        };
+
+        match self.options.checksum_method {
+            ChecksumMethod::PerLine if !self.writing_checksum_table => line_length.saturating_sub(CHECKSUM_ITEM_SIZE),
+            _ => line_length,
+        }
+    }
+
+    fn enter_struct(&mut self, name: &'static str) {
+        if self.options.annotation_method != AnnotationMethod::Disabled {
+            self.struct_path.push(StructPathSegment {
+                name,
+                field: None,
+                position: 0,
+            });
+        }
+    }
+
+    fn enter_field(&mut self, field: Option<&'static str>) {
+        if let Some(segment) = self.struct_path.last_mut() {
+            segment.field = Some(field.map_or_else(|| segment.position.to_string(), str::to_string));
+            segment.position += 1;
+        }
+    }
+
+    fn leave_struct(&mut self) {
+        self.struct_path.pop();
+    }
+
+    fn enter_compound(&mut self) {
+        self.depth += 1;
+    }
+
+    /// Ends the line after a compound value if the line-break method asks for it.
+    fn leave_compound(&mut self) -> Result<()> {
+        self.depth -= 1;
+
+        let line_break = match self.options.line_break_method {
+            LineBreakMethod::Packed | LineBreakMethod::TopLevelElement => false,
+            LineBreakMethod::Record => true,
+            LineBreakMethod::Depth(depth) => self.depth <= depth,
+        };
+
+        if line_break {
+            self.finalize_line()?;
+        }
+        Ok(())
+    }
+
+    /// Renumbers the lines with a smaller increment when they went past
+    /// `line_number_limit` under `LineNumberOverflowMethod::Compact`.
+    fn compact_line_numbers(&mut self) {
+        let lines = &mut self.basic_program.contents;
+
+        if lines.last().is_some_and(|line| line.0 > self.options.line_number_limit) {
+            let line_number_range = (self.options.line_number_limit - self.options.line_number_start) as usize;
+            let line_number_increment = line_number_range / (lines.len() - 1);
+
+            for (index, line) in lines.iter_mut().enumerate() {
+                line.0 = self.options.line_number_start + (index * line_number_increment) as u16;
+            }
+        }
+    }
+
     fn quote_and_escape(&self, s: impl ToString) -> String {
         let s = s.to_string();
 
@@ -87,22 +624,220 @@ impl Serializer {
     }
 
     fn emit_basic_data_item(&mut self, s: impl ToString) -> Result<()> {
-        let token = self.format_basic_data_item(s.to_string());
-        if let Err(_) = self.basic_next_line.push_token(token, self.options.line_length) {
-            self.finalize_line()?;
+        let s = s.to_string();
+        self.check_sentinel_collision(&s)?;
+
+        let annotation = self.struct_path_annotation();
+        self.push_data_item(s, annotation)
+    }
 
-            let token = self.format_basic_data_item(s.to_string());
-            if let Err(_) = self.basic_next_line.push_token(token, self.options.line_length) {
-                panic!("Failed to serialize token");
+    fn push_data_item(&mut self, s: String, annotation: Option<String>) -> Result<()> {
+        if let Some(buffer) = self.active_buffer() {
+            buffer.push(BufferedEvent::Item { item: s, annotation });
+            return Ok(());
+        }
+
+        let token = self.format_basic_data_item(&s);
+        match self.basic_next_line.push_token(token, self.data_line_length()) {
+            Ok(()) => {}
+            Err(basic::Error::LineOverflow) => {
+                self.finalize_line()?;
+
+                let token = self.format_basic_data_item(&s);
+                match self.basic_next_line.push_token(token, self.data_line_length()) {
+                    Ok(()) => {}
+                    Err(basic::Error::LineOverflow) => {
+                        return Err(Error::ItemTooLongForLine {
+                            line_number: self.basic_next_line_number,
+                            item: s,
+                        });
+                    }
+                    Err(err) => return Err(err.into()),
+                }
             }
+            Err(err) => return Err(err.into()),
         }
 
+        if !self.basic_next_line_started {
+            self.annotate_line(annotation);
+        }
         self.basic_next_line_started = true;
         Ok(())
     }
+
+    fn emit_length(&mut self, len: usize) -> Result<()> {
+        self.emit_basic_data_item(len)
+    }
+
+    /// Innermost buffer of a container whose length isn't known yet.
+    fn active_buffer(&mut self) -> Option<&mut Vec<BufferedEvent>> {
+        self.containers
+            .iter_mut()
+            .rev()
+            .find_map(|container| container.buffer.as_mut())
+    }
+
+    /// Writes the length prefix of a sequence or map, or starts buffering its
+    /// elements when serde doesn't know the length up front.
+    fn begin_container(&mut self, len: Option<usize>, length_prefix: bool) -> Result<()> {
+        if length_prefix {
+            if let Some(len) = len {
+                self.emit_length(len)?;
+            }
+        }
+
+        self.containers.push(Container {
+            expected_len: len.filter(|_| length_prefix),
+            len: 0,
+            buffer: if length_prefix && len.is_none() {
+                Some(vec![])
+            } else {
+                None
+            },
+            awaiting_first_item: false,
+        });
+        Ok(())
+    }
+
+    fn count_container_element(&mut self) -> Result<()> {
+        let sentinel_termination = self.sentinel().is_some();
+
+        if let Some(container) = self.containers.last_mut() {
+            if container.awaiting_first_item {
+                return Err(empty_element_error());
+            }
+
+            container.len += 1;
+            container.awaiting_first_item = sentinel_termination;
+        }
+
+        if self.options.line_break_method == LineBreakMethod::TopLevelElement && self.containers.len() == 1 {
+            self.finalize_line()?;
+        }
+        Ok(())
+    }
+
+    fn sentinel(&self) -> Option<Sentinel> {
+        match self.options.container_termination_method {
+            ContainerTerminationMethod::LengthPrefix => None,
+            ContainerTerminationMethod::Sentinel(sentinel) => Some(sentinel),
+        }
+    }
+
+    /// Checks the first data item of sequence and map elements, as the reader
+    /// compares it to the sentinel to find the end of the container.
+    fn check_sentinel_collision(&mut self, item: &str) -> Result<()> {
+        let Some(sentinel) = self.sentinel() else {
+            return Ok(());
+        };
+
+        let value = match item.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+            None => item,
+        };
+        let collides = sentinel.matches(value, &self.options.encoding_options);
+
+        for container in self.containers.iter_mut().filter(|c| c.awaiting_first_item) {
+            if collides {
+                return Err(Error::SentinelCollision(item.to_string()));
+            }
+            container.awaiting_first_item = false;
+        }
+        Ok(())
+    }
+
+    /// Checks the number of elements against the length prefix already
+    /// written, or writes the prefix followed by the buffered elements.
+    fn end_container(&mut self) -> Result<()> {
+        let container = self.containers.pop().expect("container stack is empty");
+
+        if container.awaiting_first_item {
+            return Err(empty_element_error());
+        }
+
+        if let Some(expected_len) = container.expected_len {
+            if expected_len != container.len {
+                return Err(Error::LengthMismatch {
+                    expected: expected_len,
+                    actual: container.len,
+                });
+            }
+        }
+
+        if let Some(buffer) = container.buffer {
+            self.emit_length(container.len)?;
+
+            for event in buffer {
+                match event {
+                    BufferedEvent::Item { item, annotation } => self.push_data_item(item, annotation)?,
+                    BufferedEvent::LineBreak => self.finalize_line()?,
+                }
+            }
+        }
+
+        match self.sentinel() {
+            Some(Sentinel::Number(v)) => self.emit_basic_data_item(format_f64(v, FloatOverflowMethod::Fail)?)?,
+            Some(Sentinel::String(s)) => {
+                let escaped = self.quote_and_escape(s);
+                self.emit_basic_data_item(escaped)?
+            }
+            None => {}
+        }
+
+        if self.options.line_break_method == LineBreakMethod::TopLevelElement && self.containers.is_empty() {
+            self.finalize_line()?;
+        }
+        Ok(())
+    }
+
+    fn emit_signed_integer(&mut self, v: i128, word_count: usize) -> Result<()> {
+        match self.options.large_integer_method {
+            LargeIntegerMethod::Fail if v.unsigned_abs() > MAX_EXACT_INTEGER => {
+                Err(Error::UnrepresentableInteger(v.to_string()))
+            }
+            LargeIntegerMethod::String if word_count > 1 => self.emit_basic_data_item(format!("\"{}\"", v)),
+            LargeIntegerMethod::Words if word_count > 1 => {
+                self.emit_basic_data_item(v >> ((word_count - 1) * 32))?;
+                for index in (0..word_count - 1).rev() {
+                    self.emit_basic_data_item((v >> (index * 32)) & 0xFFFF_FFFF)?;
+                }
+                Ok(())
+            }
+            _ => self.emit_basic_data_item(v),
+        }
+    }
+
+    fn emit_unsigned_integer(&mut self, v: u128, word_count: usize) -> Result<()> {
+        match self.options.large_integer_method {
+            LargeIntegerMethod::Fail if v > MAX_EXACT_INTEGER => Err(Error::UnrepresentableInteger(v.to_string())),
+            LargeIntegerMethod::String if word_count > 1 => self.emit_basic_data_item(format!("\"{}\"", v)),
+            LargeIntegerMethod::Words if word_count > 1 => {
+                for index in (0..word_count).rev() {
+                    self.emit_basic_data_item((v >> (index * 32)) & 0xFFFF_FFFF)?;
+                }
+                Ok(())
+            }
+            _ => self.emit_basic_data_item(v),
+        }
+    }
+
+    fn emit_variant(&mut self, variant_index: u32, variant: &'static str) -> Result<()> {
+        if self.options.emit_enum_names {
+            let escaped = self.quote_and_escape(variant);
+            self.emit_basic_data_item(escaped)
+        } else {
+            self.emit_basic_data_item(variant_index)
+        }
+    }
+}
+
+fn empty_element_error() -> Error {
+    Error::UnsupportedType(String::from(
+        "elements without data items can't be told apart from the sentinel",
+    ))
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -119,43 +854,53 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_signed_integer(v as i128, 1)
     }
 
     fn serialize_i16(self, v: i16) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_signed_integer(v as i128, 1)
     }
 
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_signed_integer(v as i128, 1)
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_signed_integer(v as i128, 2)
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_unsigned_integer(v as u128, 1)
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_unsigned_integer(v as u128, 1)
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_unsigned_integer(v as u128, 1)
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.emit_basic_data_item(v)
+        self.emit_unsigned_integer(v as u128, 2)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.emit_signed_integer(v, 4)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.emit_unsigned_integer(v, 4)
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.emit_basic_data_item(v)
+        let formatted = format_f32(v, self.options.float_overflow_method)?;
+        self.emit_basic_data_item(formatted)
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        self.emit_basic_data_item(v)
+        let formatted = format_f64(v, self.options.float_overflow_method)?;
+        self.emit_basic_data_item(formatted)
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
@@ -164,28 +909,66 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        let escaped = self.quote_and_escape(v);
-        self.emit_basic_data_item(escaped)
+        let length = v.chars().count();
+        if length > MAX_STRING_LENGTH {
+            return Err(Error::StringTooLong(length));
+        }
+
+        match self.options.string_split_method {
+            StringSplitMethod::Disabled => {
+                let escaped = self.quote_and_escape(v);
+                self.emit_basic_data_item(escaped)
+            }
+            StringSplitMethod::Chunked => {
+                let chars = v.chars().collect::<Vec<char>>();
+                let chunks = if chars.is_empty() {
+                    vec![String::new()]
+                } else {
+                    chars
+                        .chunks(self.options.max_string_chunk_length())
+                        .map(|chunk| chunk.iter().collect())
+                        .collect()
+                };
+
+                self.emit_basic_data_item(chunks.len())?;
+                for chunk in chunks {
+                    let escaped = self.quote_and_escape(chunk);
+                    self.emit_basic_data_item(escaped)?;
+                }
+                Ok(())
+            }
+        }
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        if self.options.container_prefix_options.sequence_length {
-            self.serialize_u64(v.len() as u64)?;
+        if let Some(bits) = bits_per_digit(self.options.byte_encoding_method) {
+            let packed = pack_bytes(v, bits, self.options.encoding_options.variant)
+                .chars()
+                .collect::<Vec<char>>();
+
+            self.emit_basic_data_item(v.len())?;
+            for chunk in packed.chunks(self.options.max_string_chunk_length()) {
+                self.emit_basic_data_item(format!("\"{}\"", chunk.iter().collect::<String>()))?;
+            }
+            return Ok(());
         }
 
+        self.begin_container(Some(v.len()), self.options.container_prefix_options.sequence_length)?;
+
         for b in v {
+            self.count_container_element()?;
             self.serialize_u8(*b)?;
         }
-        Ok(())
+        self.end_container()
     }
 
     fn serialize_none(self) -> Result<()> {
         self.serialize_unit_variant("Option", 0, "None")
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.serialize_unit_variant("Option", 1, "Some")?;
         value.serialize(self)
@@ -200,21 +983,17 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, variant: &'static str) -> Result<()> {
-        if self.options.emit_enum_names {
-            variant.serialize(self)
-        } else {
-            variant_index.serialize(self)
-        }
+        self.emit_variant(variant_index, variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         variant_index: u32,
@@ -222,36 +1001,36 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         value: &T,
     ) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        if self.options.emit_enum_names {
-            self.serialize_str(variant)?;
-        } else {
-            self.serialize_u32(variant_index)?;
-        }
+        self.emit_variant(variant_index, variant)?;
 
         value.serialize(self)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        if self.options.container_prefix_options.sequence_length {
-            self.serialize_u64(len.unwrap_or(0) as u64)?;
-        }
+        self.enter_compound();
+        self.begin_container(len, self.options.container_prefix_options.sequence_length)?;
 
         Ok(self)
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.enter_compound();
+
         if self.options.container_prefix_options.tuple_length {
-            self.serialize_u64(len as u64)?;
+            self.emit_length(len)?;
         }
 
         Ok(self)
     }
 
-    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct> {
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct> {
+        self.enter_compound();
+        self.enter_struct(name);
+
         if self.options.container_prefix_options.tuple_length {
-            self.serialize_u64(len as u64)?;
+            self.emit_length(len)?;
         }
 
         Ok(self)
@@ -264,28 +1043,28 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        if self.options.emit_enum_names {
-            self.serialize_str(variant)?;
-        } else {
-            self.serialize_u32(variant_index)?;
-        }
+        self.emit_variant(variant_index, variant)?;
+        self.enter_compound();
+        self.enter_struct(variant);
 
         if self.options.container_prefix_options.tuple_length {
-            self.serialize_u64(len as u64)?;
+            self.emit_length(len)?;
         }
 
         Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        if self.options.container_prefix_options.map_length {
-            self.serialize_u64(len.unwrap_or(0) as u64)?;
-        }
+        self.enter_compound();
+        self.begin_container(len, self.options.container_prefix_options.map_length)?;
 
         Ok(self)
     }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.enter_compound();
+        self.enter_struct(name);
+
         Ok(self)
     }
 
@@ -296,131 +1075,1024 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        if self.options.emit_enum_names {
-            self.serialize_str(variant)?;
-        } else {
-            self.serialize_u32(variant_index)?;
-        }
+        self.emit_variant(variant_index, variant)?;
+        self.enter_compound();
+        self.enter_struct(variant);
 
         Ok(self)
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.count_container_element()?;
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.finalize_line()
+        self.end_container()?;
+        self.leave_compound()
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.finalize_line()
+        self.leave_compound()
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.enter_field(None);
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
+        self.leave_compound()?;
+        self.leave_struct();
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.enter_field(None);
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
+        self.leave_compound()?;
+        self.leave_struct();
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl ser::SerializeMap for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.count_container_element()?;
         key.serialize(&mut **self)
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.finalize_line()
+        self.end_container()?;
+        self.leave_compound()
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.enter_field(Some(key));
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.finalize_line()
+        self.leave_compound()?;
+        self.leave_struct();
+        Ok(())
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        self.enter_field(Some(key));
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
+        self.leave_compound()?;
+        self.leave_struct();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use basic::{BasicKeyword, BasicLine, BasicProgram, Machine, MemoryConfiguration};
+    use serde::ser::{self, SerializeSeq};
+    use serde::{de, Deserialize, Serialize};
+
+    use crate::de::from_slice;
+    use crate::error::Error;
+    use crate::options::{
+        AnnotationMethod, ByteEncodingMethod, ChecksumMethod, ContainerPrefixOptions, ContainerTerminationMethod,
+        LargeIntegerMethod, LineBreakMethod, LineNumberOverflowMethod, LoaderOptions, MemoryBudgetMethod, Options,
+        Sentinel, StringSplitMethod,
+    };
+    use crate::ser::{merge_into, to_vec, to_writer, Serializer};
+
+    #[test]
+    pub fn test_errors() {
+        let options = Options {
+            line_length: 16,
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            to_writer(vec![], &"THIS STRING IS TOO LONG", options),
+            Err(Error::ItemTooLongForLine { line_number: 1000, .. })
+        ));
+
+        let options = Options {
+            line_length: 251,
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            to_writer(vec![], &[1, 2, 3], options),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let options = Options {
+            line_number_start: 64000,
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            to_writer(vec![], &[1, 2, 3], options),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        assert!(matches!(
+            to_writer(vec![], &"X".repeat(256), Options::default()),
+            Err(Error::StringTooLong(256))
+        ));
+    }
+
+    #[test]
+    pub fn test_into_program() {
+        let options = Options {
+            line_length: 16,
+            ..Options::default()
+        };
+
+        let mut serializer = Serializer::new(options).unwrap();
+        (1, "FIRST").serialize(&mut serializer).unwrap();
+        vec![2, 3, 4, 5].serialize(&mut serializer).unwrap();
+
+        let mut basic_program = serializer.into_program().unwrap();
+        assert_eq!(basic_program.contents.len(), 2);
+        assert_eq!(basic_program.contents[1].0, 1001);
+
+        basic_program
+            .contents
+            .insert(0, BasicLine(10, vec![BasicKeyword::Rem.into(), " DATA".into()]));
+        assert_eq!(
+            from_slice::<(u8, String, Vec<u8>)>(&basic_program.to_bytes(), options).unwrap(),
+            (1, String::from("FIRST"), vec![2, 3, 4, 5])
+        );
+
+        let mut prg_bin = vec![];
+        to_writer(&mut prg_bin, &[1, 2, 3], options).unwrap();
+        assert_eq!(to_vec(&[1, 2, 3], options).unwrap(), prg_bin);
+    }
+
+    #[test]
+    pub fn test_merge_into() {
+        let options = Options {
+            line_number_limit: 1999,
+            ..Options::default()
+        };
+
+        let mut serializer = Serializer::new(options).unwrap();
+        vec![1, 2, 3].serialize(&mut serializer).unwrap();
+        let mut basic_program = serializer.into_program().unwrap();
+        basic_program.contents.splice(
+            0..0,
+            [
+                BasicLine(10, vec![BasicKeyword::Read.into(), " N".into()]),
+                BasicLine(20, vec![BasicKeyword::Print.into(), " N".into()]),
+            ],
+        );
+        basic_program
+            .contents
+            .push(BasicLine(5000, vec![BasicKeyword::End.into()]));
+
+        let mut loaded_program = BasicProgram::from_bytes(&basic_program.to_bytes(), options.encoding_options).unwrap();
+        merge_into(&mut loaded_program, &(String::from("MERGED"), vec![4, 5]), options).unwrap();
+        assert_eq!(
+            loaded_program.contents.iter().map(|line| line.0).collect::<Vec<_>>(),
+            vec![10, 20, 1000, 5000]
+        );
+        assert_eq!(
+            from_slice::<(String, Vec<u8>)>(&loaded_program.to_bytes(), options).unwrap(),
+            (String::from("MERGED"), vec![4, 5])
+        );
+
+        loaded_program
+            .contents
+            .insert(3, BasicLine(1500, vec![BasicKeyword::Goto.into(), " 10".into()]));
+        assert!(matches!(
+            merge_into(&mut loaded_program, &[6, 7], options),
+            Err(Error::Basic(basic::Error::LineRangeOverlap(1500)))
+        ));
+
+        loaded_program.contents[3] = BasicLine(
+            1500,
+            vec![
+                BasicKeyword::Data.into(),
+                " 1:".into(),
+                BasicKeyword::Goto.into(),
+                " 10".into(),
+            ],
+        );
+        assert!(matches!(
+            merge_into(&mut loaded_program, &[6, 7], options),
+            Err(Error::Basic(basic::Error::LineRangeOverlap(1500)))
+        ));
+    }
+
+    #[test]
+    pub fn test_emit_loader() {
+        let options = Options::default();
+        let data = (String::from("POINTS"), vec![(1u8, 2u8), (3, 4)]);
+
+        let mut serializer = Serializer::new(options).unwrap();
+        serializer
+            .emit_loader::<(String, Vec<(u8, u8)>)>(LoaderOptions::default())
+            .unwrap();
+        data.serialize(&mut serializer).unwrap();
+
+        let basic_program = serializer.into_program().unwrap();
+        assert_eq!(basic_program.contents[0].0, 10);
+        assert!(basic_program.contents.iter().any(|line| line.0 == 1000));
+        assert_eq!(
+            from_slice::<(String, Vec<(u8, u8)>)>(&basic_program.to_bytes(), options).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    pub fn test_line_breaks() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Point(u8, u8);
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Polygon {
+            closed: bool,
+            points: Vec<Point>,
+        }
+
+        let data = vec![
+            Polygon {
+                closed: true,
+                points: vec![Point(1, 2), Point(3, 4)],
+            },
+            Polygon {
+                closed: false,
+                points: vec![Point(5, 6)],
+            },
+        ];
+
+        for (line_break_method, items_per_line) in [
+            (LineBreakMethod::Packed, vec![11]),
+            (LineBreakMethod::Record, vec![5, 2, 4]),
+            (LineBreakMethod::TopLevelElement, vec![1, 6, 4]),
+            (LineBreakMethod::Depth(0), vec![11]),
+            (LineBreakMethod::Depth(1), vec![7, 4]),
+        ] {
+            let options = Options {
+                line_break_method,
+                ..Options::default()
+            };
+
+            let mut serializer = Serializer::new(options).unwrap();
+            data.serialize(&mut serializer).unwrap();
+            let basic_program = serializer.into_program().unwrap();
+            assert_eq!(
+                basic_program
+                    .contents
+                    .iter()
+                    .map(|line| line.1.len() - 1)
+                    .collect::<Vec<_>>(),
+                items_per_line
+            );
+            assert_eq!(
+                from_slice::<Vec<Polygon>>(&basic_program.to_bytes(), options).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_annotations() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Person {
+            first_name: String,
+            birth_year: u16,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Club {
+            name: String,
+            members: Vec<Person>,
+        }
+
+        let data = Club {
+            name: String::from("CHESS"),
+            members: vec![
+                Person {
+                    first_name: String::from("ADA"),
+                    birth_year: 1815,
+                },
+                Person {
+                    first_name: String::from("ALAN"),
+                    birth_year: 1912,
+                },
+            ],
+        };
+
+        let expected_lines: [&[&[u8]]; 2] = [
+            &[
+                b"\xE8\x03\x8F CLUB.NAME\0",
+                b"\xE9\x03\x83 CHESS, 2, ADA, 1815\0",
+                b"\xEA\x03\x8F CLUB.MEMBERS/PERSON.FIRST-NAME\0",
+            ],
+            &[
+                b"\xE8\x03\x83 CHESS, 2, ADA, 1815:\x8F CLUB.NAME\0",
+                b"\xE9\x03\x83 ALAN:\x8F CLUB.MEMBERS/PERSON.FIRST-NAME\0",
+            ],
+        ];
+
+        for (annotation_method, expected_lines) in [AnnotationMethod::RemLines, AnnotationMethod::TrailingRem]
+            .into_iter()
+            .zip(expected_lines)
+        {
+            let options = Options {
+                line_length: 40,
+                annotation_method,
+                ..Options::default()
+            };
+
+            let prg_bin = to_vec(&data, options).unwrap();
+            for line in expected_lines {
+                assert!(prg_bin.windows(line.len()).any(|window| window == *line));
+            }
+            assert_eq!(from_slice::<Club>(&prg_bin, options).unwrap(), data);
+        }
+    }
+
+    #[test]
+    pub fn test_unknown_lengths() {
+        struct EvenNumbers(u8);
+
+        impl Serialize for EvenNumbers {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_seq((0..self.0).filter(|v| v % 2 == 0))
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Inner {
+            b: u8,
+            c: u8,
+        }
+
+        #[derive(Serialize)]
+        struct Outer {
+            a: u8,
+            #[serde(flatten)]
+            inner: Inner,
+        }
+
+        let options = Options {
+            line_length: 16,
+            ..Options::default()
+        };
+
+        let data = (EvenNumbers(20), vec![EvenNumbers(4), EvenNumbers(0)]);
+        let prg_bin = to_vec(&data, options).unwrap();
+        assert_eq!(
+            from_slice::<(Vec<u8>, Vec<Vec<u8>>)>(&prg_bin, options).unwrap(),
+            ((0..20).step_by(2).collect(), vec![vec![0, 2], vec![]])
+        );
+
+        let data = Outer {
+            a: 1,
+            inner: Inner { b: 2, c: 3 },
+        };
+        let prg_bin = to_vec(&data, options).unwrap();
+        assert_eq!(
+            from_slice::<HashMap<String, u8>>(&prg_bin, options).unwrap(),
+            HashMap::from([(String::from("A"), 1), (String::from("B"), 2), (String::from("C"), 3)])
+        );
+
+        struct WrongLength;
+
+        impl Serialize for WrongLength {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                let mut seq = serializer.serialize_seq(Some(3))?;
+                seq.serialize_element(&1)?;
+                seq.serialize_element(&2)?;
+                seq.end()
+            }
+        }
+
+        assert!(matches!(
+            to_vec(&WrongLength, options),
+            Err(Error::LengthMismatch { expected: 3, actual: 2 })
+        ));
+    }
+
+    #[test]
+    pub fn test_sentinel_termination() {
+        let options = Options {
+            container_prefix_options: ContainerPrefixOptions {
+                sequence_length: false,
+                map_length: false,
+                tuple_length: false,
+            },
+            container_termination_method: ContainerTerminationMethod::Sentinel(Sentinel::Number(-1.0)),
+            ..Options::default()
+        };
+
+        let data = (vec![1, 2, 3], vec![String::from("A")], BTreeMap::from([(5, 6)]));
+        let prg_bin = to_vec(&data, options).unwrap();
+        assert_eq!(&prg_bin[7..19], b" 1, 2, 3, -1");
+        assert_eq!(
+            from_slice::<(Vec<u8>, Vec<String>, BTreeMap<u8, u8>)>(&prg_bin, options).unwrap(),
+            data
+        );
+
+        assert!(matches!(
+            to_vec(&vec![0.0, -1.0], options),
+            Err(Error::SentinelCollision(_))
+        ));
+        assert!(matches!(
+            to_vec(&vec![vec![4], vec![]], options),
+            Err(Error::SentinelCollision(_))
+        ));
+        assert!(matches!(to_vec(&vec![()], options), Err(Error::UnsupportedType(_))));
+
+        let options = Options {
+            container_termination_method: ContainerTerminationMethod::Sentinel(Sentinel::String("END")),
+            ..options
+        };
+
+        let data = vec![vec![String::from("BEGIN")], vec![String::from("A"), String::from("B")]];
+        let prg_bin = to_vec(&data, options).unwrap();
+        assert_eq!(from_slice::<Vec<Vec<String>>>(&prg_bin, options).unwrap(), data);
+        assert!(matches!(
+            to_vec(&vec!["end"], options),
+            Err(Error::SentinelCollision(_))
+        ));
+
+        for sentinel in ["#END", "\"END"] {
+            let options = Options {
+                container_termination_method: ContainerTerminationMethod::Sentinel(Sentinel::String(sentinel)),
+                ..options
+            };
+            assert!(matches!(to_vec(&vec!["A"], options), Err(Error::InvalidOptions(_))));
+            assert!(matches!(
+                Serializer::new(options).and_then(|mut s| s.emit_loader::<Vec<String>>(LoaderOptions::default())),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+
+        let options = Options {
+            container_prefix_options: ContainerPrefixOptions::default(),
+            ..options
+        };
+
+        assert!(matches!(to_vec(&vec![1], options), Err(Error::InvalidOptions(_))));
+    }
+
+    #[test]
+    pub fn test_checksums() {
+        let data = vec![(String::from("JOHN"), 1970u16), (String::from("JANE"), 1982)];
+
+        let options = Options {
+            checksum_method: ChecksumMethod::PerLine,
+            ..Options::default()
+        };
+
+        let mut serializer = Serializer::new(options).unwrap();
+        assert_eq!(serializer.emit_verifier(LoaderOptions::default()).unwrap(), 20);
+        data.serialize(&mut serializer).unwrap();
+        let basic_program = serializer.into_program().unwrap();
+        assert_eq!(basic_program.contents[0].0, 10);
+        assert_eq!(basic_program.contents.iter().filter(|line| line.0 >= 1000).count(), 2);
+
+        let mut prg_bin = basic_program.to_bytes();
+        assert_eq!(from_slice::<Vec<(String, u16)>>(&prg_bin, options).unwrap(), data);
+
+        let position = prg_bin.windows(4).position(|w| w == b"JANE").unwrap();
+        prg_bin[position] = b'D';
+        assert!(matches!(
+            from_slice::<Vec<(String, u16)>>(&prg_bin, options),
+            Err(Error::ChecksumMismatch(1001))
+        ));
+
+        let chunked_options = Options {
+            checksum_method: ChecksumMethod::PerLine,
+            string_split_method: StringSplitMethod::Chunked,
+            line_length: 40,
+            ..Options::default()
+        };
+        let long_string = "A".repeat(100);
+        let prg_bin = to_vec(&long_string, chunked_options).unwrap();
+        assert_eq!(from_slice::<String>(&prg_bin, chunked_options).unwrap(), long_string);
+
+        let mut serializer = Serializer::new(options).unwrap();
+        assert!(matches!(
+            serializer.emit_loader::<Vec<(String, u16)>>(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let options = Options {
+            checksum_method: ChecksumMethod::Table,
+            line_length: 24,
+            ..Options::default()
+        };
+
+        let mut serializer = Serializer::new(options).unwrap();
+        serializer
+            .emit_loader::<Vec<(String, u16)>>(LoaderOptions::default())
+            .unwrap();
+        serializer
+            .emit_verifier(LoaderOptions {
+                line_number_start: 500,
+                ..LoaderOptions::default()
+            })
+            .unwrap();
+        data.serialize(&mut serializer).unwrap();
+        let basic_program = serializer.into_program().unwrap();
+        assert_eq!(basic_program.contents.iter().filter(|line| line.0 >= 1000).count(), 3);
+        assert_eq!(
+            from_slice::<Vec<(String, u16)>>(&basic_program.to_bytes(), options).unwrap(),
+            data
+        );
+
+        let mut serializer = Serializer::new(Options::default()).unwrap();
+        assert!(matches!(
+            serializer.emit_verifier(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let mut serializer = Serializer::new(options).unwrap();
+        serializer
+            .emit_loader::<Vec<(String, u16)>>(LoaderOptions::default())
+            .unwrap();
+        serializer.emit_verifier(LoaderOptions::default()).unwrap();
+        assert!(matches!(serializer.into_program(), Err(Error::InvalidOptions(_))));
+    }
+
+    #[test]
+    pub fn test_line_number_overflow() {
+        let data = (0..40).collect::<Vec<u8>>();
+
+        let options = Options {
+            line_length: 24,
+            line_number_start: 63990,
+            line_number_increment: 10,
+            ..Options::default()
+        };
+
+        assert!(matches!(
+            to_writer(vec![], &data, options),
+            Err(Error::LineNumberOverflow)
+        ));
+
+        let options = Options {
+            line_number_overflow: LineNumberOverflowMethod::Compact,
+            ..options
+        };
+
+        let mut prg_bin = vec![];
+        to_writer(&mut prg_bin, &data, options).unwrap();
+        assert_eq!(from_slice::<Vec<u8>>(&prg_bin, options).unwrap(), data);
+        assert_eq!(&prg_bin[4..6], &63990u16.to_le_bytes());
+
+        let options = Options {
+            line_number_limit: 63992,
+            ..options
+        };
+
+        assert!(matches!(
+            to_writer(vec![], &data, options),
+            Err(Error::LineNumberOverflow)
+        ));
+    }
+
+    #[test]
+    pub fn test_large_integers() {
+        let data = (u64::MAX, i64::MIN, 12345678901234i64, -1i128, u128::MAX, 4294967296u64);
+
+        for large_integer_method in [LargeIntegerMethod::String, LargeIntegerMethod::Words] {
+            let options = Options {
+                large_integer_method,
+                ..Options::default()
+            };
+
+            let mut prg_bin = vec![];
+            to_writer(&mut prg_bin, &data, options).unwrap();
+            assert_eq!(
+                from_slice::<(u64, i64, i64, i128, u128, u64)>(&prg_bin, options).unwrap(),
+                data
+            );
+        }
+
+        let options = Options {
+            large_integer_method: LargeIntegerMethod::Words,
+            ..Options::default()
+        };
+
+        let mut prg_bin = vec![];
+        to_writer(&mut prg_bin, &(12345678901234i64, -2i64), options).unwrap();
+        assert_eq!(&prg_bin[7..prg_bin.len() - 3], b" 2874, 1942892530, -1, 4294967294");
+
+        assert!(matches!(
+            to_writer(vec![], &4294967297u64, Options::default()),
+            Err(Error::UnrepresentableInteger(_))
+        ));
+        assert!(to_writer(vec![], &-4294967296i64, Options::default()).is_ok());
+    }
+
+    #[test]
+    pub fn test_string_split() {
+        let options = Options {
+            line_length: 16,
+            string_split_method: StringSplitMethod::Chunked,
+            ..Options::default()
+        };
+
+        let data = (
+            String::from("HELLO"),
+            String::from("LOREM IPSUM DOLOR"),
+            "X".repeat(255),
+        );
+
+        let mut prg_bin = vec![];
+        to_writer(&mut prg_bin, &data, options).unwrap();
+        assert_eq!(from_slice::<(String, String, String)>(&prg_bin, options).unwrap(), data);
+
+        #[rustfmt::skip]
+        assert_eq!(
+            &prg_bin[..48],
+            &[
+                0x01, 0x08, 0x13, 0x08, 0xE8, 0x03, 0x83, 0x20, 0x31, 0x2C, 0x20, 0x48, 0x45, 0x4C, 0x4C, 0x4F,
+                0x2C, 0x20, 0x32, 0x00, 0x28, 0x08, 0xE9, 0x03, 0x83, 0x20, 0x22, 0x4C, 0x4F, 0x52, 0x45, 0x4D,
+                0x20, 0x49, 0x50, 0x53, 0x55, 0x4D, 0x20, 0x22, 0x00, 0x38, 0x08, 0xEA, 0x03, 0x83, 0x20, 0x44,
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_packed_bytes() {
+        struct ByteBuf(Vec<u8>);
+
+        impl Serialize for ByteBuf {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for ByteBuf {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                struct ByteBufVisitor;
+
+                impl de::Visitor<'_> for ByteBufVisitor {
+                    type Value = ByteBuf;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str("bytes")
+                    }
+
+                    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<ByteBuf, E> {
+                        Ok(ByteBuf(v.to_vec()))
+                    }
+                }
+
+                deserializer.deserialize_byte_buf(ByteBufVisitor)
+            }
+        }
+
+        let options = Options {
+            byte_encoding_method: ByteEncodingMethod::Hex,
+            ..Options::default()
+        };
+        let prg_bin = to_vec(&(ByteBuf(vec![1, 2, 255]), 7u8), options).unwrap();
+        assert_eq!(&prg_bin[6..23], b"\x83 3, \"0102FF\", 7\x00");
+
+        let (bytes, v) = from_slice::<(ByteBuf, u8)>(&prg_bin, options).unwrap();
+        assert_eq!((bytes.0, v), (vec![1, 2, 255], 7));
+
+        let sprite = (0..63).map(|i| (i * 37) as u8).collect::<Vec<u8>>();
+        let line_count = |options: Options| {
+            let mut serializer = Serializer::new(options).unwrap();
+            ByteBuf(sprite.clone()).serialize(&mut serializer).unwrap();
+            serializer.into_program().unwrap().contents.len()
+        };
+
+        let options = Options {
+            line_length: 40,
+            byte_encoding_method: ByteEncodingMethod::Base32,
+            ..Options::default()
+        };
+        assert_eq!(
+            line_count(Options {
+                line_length: 40,
+                ..Options::default()
+            }),
+            8
+        );
+        assert_eq!(line_count(options), 4);
+
+        let mut serializer = Serializer::new(options).unwrap();
+        assert_eq!(serializer.emit_unpacker(LoaderOptions::default()).unwrap(), 20);
+        ByteBuf(sprite.clone()).serialize(&mut serializer).unwrap();
+        let basic_program = serializer.into_program().unwrap();
+        assert_eq!(basic_program.contents[0].0, 10);
+        assert_eq!(
+            from_slice::<ByteBuf>(&basic_program.to_bytes(), options).unwrap().0,
+            sprite
+        );
+
+        let mut serializer = Serializer::new(options).unwrap();
+        assert!(matches!(
+            serializer.emit_loader::<ByteBuf>(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let mut serializer = Serializer::new(Options::default()).unwrap();
+        assert!(matches!(
+            serializer.emit_unpacker(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let options = Options {
+            container_prefix_options: ContainerPrefixOptions {
+                sequence_length: false,
+                map_length: false,
+                tuple_length: false,
+            },
+            container_termination_method: ContainerTerminationMethod::Sentinel(Sentinel::Number(-1.0)),
+            ..options
+        };
+        assert!(matches!(
+            to_vec(&ByteBuf(vec![1, 2, 255]), options),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_streaming() {
+        #[derive(Serialize, Deserialize)]
+        struct Person {
+            name: String,
+            birth_year: u16,
+            scores: BTreeMap<String, f32>,
+        }
+
+        let data = (0..50)
+            .map(|i| Person {
+                name: format!("PERSON {}", i),
+                birth_year: 1950 + i,
+                scores: BTreeMap::from([(String::from("A"), i as f32 / 4.0), (String::from("B"), -1.5)]),
+            })
+            .collect::<Vec<_>>();
+
+        let stream = |options: Options| {
+            let mut prg_bin = vec![];
+            to_writer(&mut prg_bin, &data, options).unwrap();
+            prg_bin
+        };
+
+        for options in [
+            Options::default(),
+            Options {
+                line_length: 30,
+                annotation_method: AnnotationMethod::TrailingRem,
+                ..Options::default()
+            },
+            Options {
+                annotation_method: AnnotationMethod::RemLines,
+                checksum_method: ChecksumMethod::Table,
+                line_break_method: LineBreakMethod::Packed,
+                ..Options::default()
+            },
+            Options {
+                line_number_start: 63900,
+                line_number_increment: 50,
+                line_number_overflow: LineNumberOverflowMethod::Compact,
+                ..Options::default()
+            },
+        ] {
+            assert_eq!(stream(options), to_vec(&data, options).unwrap());
+        }
+
+        let options = Options {
+            byte_encoding_method: ByteEncodingMethod::Hex,
+            ..Options::default()
+        };
+
+        let mut serializer = Serializer::new(options).unwrap();
+        serializer.emit_loader::<Vec<Person>>(LoaderOptions::default()).unwrap();
+        serializer
+            .emit_unpacker(LoaderOptions {
+                line_number_start: 900,
+                ..LoaderOptions::default()
+            })
+            .unwrap();
+        data.serialize(&mut serializer).unwrap();
+        let prg_bin = serializer.into_program().unwrap().to_bytes();
+
+        let mut streamed_prg_bin = vec![];
+        let mut serializer = Serializer::with_writer(&mut streamed_prg_bin, options).unwrap();
+        serializer.emit_loader::<Vec<Person>>(LoaderOptions::default()).unwrap();
+        serializer
+            .emit_unpacker(LoaderOptions {
+                line_number_start: 900,
+                ..LoaderOptions::default()
+            })
+            .unwrap();
+        data.serialize(&mut serializer).unwrap();
+        assert!(matches!(
+            serializer.emit_loader::<Vec<Person>>(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+        serializer.finish_writer().unwrap();
+        assert_eq!(streamed_prg_bin, prg_bin);
+
+        let options = Options {
+            checksum_method: ChecksumMethod::PerLine,
+            ..Options::default()
+        };
+        let mut serializer = Serializer::with_writer(vec![], options).unwrap();
+        assert!(matches!(
+            serializer.emit_verifier(LoaderOptions::default()),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(matches!(serializer.into_program(), Err(Error::InvalidOptions(_))));
+
+        let options = Options {
+            line_number_overflow: LineNumberOverflowMethod::Compact,
+            ..Options::default()
+        };
+        assert!(matches!(
+            Serializer::with_writer(vec![], options),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_memory_budget() {
+        let data = (0..2000u16).collect::<Vec<_>>();
+        let prg_bin = to_vec(&data, Options::default()).unwrap();
+        let size = prg_bin.len() - 2;
+
+        let budget = |reserved: usize| Options {
+            memory_budget_method: MemoryBudgetMethod::Limited {
+                memory: MemoryConfiguration::C64,
+                reserved,
+            },
+            ..Options::default()
+        };
+
+        let reserved = MemoryConfiguration::C64.size() - size;
+        assert_eq!(to_vec(&data, budget(reserved)).unwrap(), prg_bin);
+        assert!(matches!(
+            to_vec(&data, budget(reserved + 1)),
+            Err(Error::ProgramTooLarge { size: s, available }) if s <= size && available == size - 1
+        ));
+        assert!(matches!(
+            to_writer(vec![], &data, budget(reserved + 1)),
+            Err(Error::ProgramTooLarge { .. })
+        ));
+
+        let mut serializer = Serializer::new(budget(reserved)).unwrap();
+        serializer.emit_loader::<Vec<u16>>(LoaderOptions::default()).unwrap();
+        data.serialize(&mut serializer).unwrap();
+        assert!(matches!(
+            serializer.into_program(),
+            Err(Error::ProgramTooLarge { size: s, available }) if s > size && available == size
+        ));
+
+        let mut basic_program = BasicProgram::from_bytes(&prg_bin, Options::default().encoding_options).unwrap();
+        basic_program.contents.insert(
+            0,
+            BasicLine(10, vec![BasicKeyword::Rem.into(), "X".repeat(50).as_str().into()]),
+        );
+        let program_bin = basic_program.to_bytes();
+        assert!(matches!(
+            merge_into(&mut basic_program, &data, budget(reserved)),
+            Err(Error::ProgramTooLarge { available, .. }) if available == size
+        ));
+        assert_eq!(basic_program.to_bytes(), program_bin);
+        assert!(merge_into(&mut basic_program, &data, budget(reserved - 60)).is_ok());
+
+        let memory = MemoryConfiguration {
+            start: 0x1001,
+            end: 0x4000,
+        };
+        let options = Options {
+            memory_budget_method: MemoryBudgetMethod::Limited { memory, reserved: 0 },
+            ..Options::default()
+        };
+        assert_eq!(basic_program.free_bytes(&memory), None);
+        assert!(matches!(
+            to_vec(&1u8, options),
+            Err(Error::ProgramTooLarge { available: 0, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_machines() {
+        for (machine, prg_header) in [
+            (Machine::C64, [0x01, 0x08, 0x0E, 0x08]),
+            (Machine::Vic20, [0x01, 0x10, 0x0E, 0x10]),
+            (Machine::Vic20Expanded, [0x01, 0x12, 0x0E, 0x12]),
+            (Machine::Pet, [0x01, 0x04, 0x0E, 0x04]),
+            (Machine::C16, [0x01, 0x10, 0x0E, 0x10]),
+            (Machine::Plus4, [0x01, 0x10, 0x0E, 0x10]),
+            (Machine::C128, [0x01, 0x1C, 0x0E, 0x1C]),
+        ] {
+            let options = Options::for_machine(machine);
+            let prg_bin = to_vec(&(12u8, 34u8), options).unwrap();
+            assert_eq!(prg_bin[..4], prg_header);
+            assert_eq!(from_slice::<(u8, u8)>(&prg_bin, options).unwrap(), (12, 34));
+
+            let mut serializer = Serializer::new(options).unwrap();
+            (12u8, 34u8).serialize(&mut serializer).unwrap();
+            let basic_program = serializer.into_program().unwrap();
+            assert_eq!(basic_program.dialect(), &machine.dialect());
+            assert!(basic_program.to_listing().starts_with("1000 DATA"));
+
+            let max_line_length = machine.max_line_length();
+            assert_eq!(options.line_length, max_line_length);
+            let prg_bin = to_vec(&vec![1000u16; 100], options).unwrap();
+            let basic_program = BasicProgram::from_bytes(&prg_bin, options.encoding_options).unwrap();
+            assert!(basic_program.contents.iter().all(|line| line.size() <= max_line_length));
+
+            let too_long_options = Options {
+                line_length: max_line_length + 1,
+                ..options
+            };
+            assert!(matches!(
+                to_vec(&(12u8, 34u8), too_long_options),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+
+        let data = vec![0xFFFFu16; 2000];
+        assert!(to_vec(&data, Options::for_machine(Machine::C64)).is_ok());
+        assert!(matches!(
+            to_vec(&data, Options::for_machine(Machine::Vic20)),
+            Err(Error::ProgramTooLarge { available: 3583, .. })
+        ));
+    }
+}
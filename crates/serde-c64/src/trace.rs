@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Layout of the data items written for a type, as seen by `READ`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Unit,
+    Number,
+    LargeInteger(usize),
+    String,
+    Bytes,
+    Option(Box<Shape>),
+    Seq(Box<Shape>),
+    Tuple(Vec<Shape>),
+    Map(Box<Shape>, Box<Shape>),
+    Struct(Vec<(&'static str, Shape)>),
+    Enum(&'static str),
+}
+
+/// Variants of an enum with the shapes of their contents.
+pub type EnumVariants = Vec<(&'static str, Shape)>;
+
+/// Records the shape of `T` by driving its `Deserialize` implementation.
+///
+/// Every type is traced with a single element for sequences and maps. Enums
+/// are traced once per variant, re-running the whole trace with a different
+/// variant chosen until every variant of every reachable enum is known.
+///
+/// Enums are explored in the order they are found. A nested enum is reached
+/// again by restoring the choices of the enums around it when it was found.
+pub fn trace<'de, T>() -> Result<(Shape, HashMap<&'static str, EnumVariants>)>
+where
+    T: Deserialize<'de>,
+{
+    let mut enum_variants: HashMap<&'static str, Vec<Option<(&'static str, Shape)>>> = HashMap::new();
+    let mut enum_paths = vec![];
+    let mut enum_choices = HashMap::new();
+    let mut root_shape = None;
+    let mut explored_count = 0;
+
+    loop {
+        let mut tracer = Tracer {
+            enum_choices: &enum_choices,
+            enum_variants: &mut enum_variants,
+            enum_paths: &mut enum_paths,
+            enum_stack: vec![],
+            struct_stack: vec![],
+            shapes: vec![],
+        };
+
+        T::deserialize(&mut tracer)?;
+        let shape = tracer.shapes.pop().unwrap_or(Shape::Unit);
+        root_shape.get_or_insert(shape);
+
+        let unexplored = enum_paths.iter().find_map(|(name, path)| {
+            enum_variants[name]
+                .iter()
+                .position(Option::is_none)
+                .map(|index| (*name, path, index))
+        });
+
+        let Some((name, path, index)) = unexplored else {
+            break;
+        };
+
+        // Every pass must explore a new variant, or the next one would be the same
+        let count = enum_variants.values().flatten().filter(|v| v.is_some()).count();
+        if count == explored_count {
+            return Err(unsupported(&format!("unreachable variant of `{}`", name)));
+        }
+        explored_count = count;
+
+        for (parent, choice) in path {
+            enum_choices.insert(*parent, *choice);
+        }
+        enum_choices.insert(name, index);
+    }
+
+    let enum_variants = enum_variants
+        .into_iter()
+        .map(|(name, variants)| (name, variants.into_iter().flatten().collect()))
+        .collect();
+
+    Ok((root_shape.unwrap(), enum_variants))
+}
+
+struct Tracer<'a> {
+    enum_choices: &'a HashMap<&'static str, usize>,
+    enum_variants: &'a mut HashMap<&'static str, Vec<Option<(&'static str, Shape)>>>,
+    /// Enums in the order they were found, with the choices of the enums
+    /// around them at the time.
+    enum_paths: &'a mut Vec<(&'static str, Vec<(&'static str, usize)>)>,
+    enum_stack: Vec<&'static str>,
+    struct_stack: Vec<&'static str>,
+    shapes: Vec<Shape>,
+}
+
+impl Tracer<'_> {
+    /// Runs `f` with an empty shape list and returns the shapes it recorded.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<(T, Vec<Shape>)> {
+        let parent_shapes = std::mem::take(&mut self.shapes);
+        let value = f(self);
+        let shapes = std::mem::replace(&mut self.shapes, parent_shapes);
+        Ok((value?, shapes))
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::UnsupportedType(format!("{} can't be read by a BASIC loader", what))
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $visit:ident, $value:expr, $shape:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            self.shapes.push($shape);
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Tracer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(unsupported("self-describing data"))
+    }
+
+    trace_primitive!(deserialize_bool, visit_bool, false, Shape::Number);
+    trace_primitive!(deserialize_i8, visit_i8, 1, Shape::Number);
+    trace_primitive!(deserialize_i16, visit_i16, 1, Shape::Number);
+    trace_primitive!(deserialize_i32, visit_i32, 1, Shape::Number);
+    trace_primitive!(deserialize_i64, visit_i64, 1, Shape::LargeInteger(2));
+    trace_primitive!(deserialize_i128, visit_i128, 1, Shape::LargeInteger(4));
+    trace_primitive!(deserialize_u8, visit_u8, 1, Shape::Number);
+    trace_primitive!(deserialize_u16, visit_u16, 1, Shape::Number);
+    trace_primitive!(deserialize_u32, visit_u32, 1, Shape::Number);
+    trace_primitive!(deserialize_u64, visit_u64, 1, Shape::LargeInteger(2));
+    trace_primitive!(deserialize_u128, visit_u128, 1, Shape::LargeInteger(4));
+    trace_primitive!(deserialize_f32, visit_f32, 1.0, Shape::Number);
+    trace_primitive!(deserialize_f64, visit_f64, 1.0, Shape::Number);
+    trace_primitive!(deserialize_char, visit_char, 'A', Shape::String);
+    trace_primitive!(deserialize_str, visit_borrowed_str, "", Shape::String);
+    trace_primitive!(deserialize_string, visit_borrowed_str, "", Shape::String);
+    trace_primitive!(deserialize_bytes, visit_borrowed_bytes, &[], Shape::Bytes);
+    trace_primitive!(deserialize_byte_buf, visit_borrowed_bytes, &[], Shape::Bytes);
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.shapes.push(Shape::Unit);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (value, mut shapes) = self.nested(|tracer| visitor.visit_some(tracer))?;
+        self.shapes
+            .push(Shape::Option(Box::new(shapes.pop().unwrap_or(Shape::Unit))));
+        Ok(value)
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.shapes.push(Shape::Unit);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (value, mut shapes) = self.nested(|tracer| visitor.visit_seq(TraceAccess { tracer, remaining: 1 }))?;
+        self.shapes
+            .push(Shape::Seq(Box::new(shapes.pop().unwrap_or(Shape::Unit))));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (value, shapes) = self.nested(|tracer| visitor.visit_seq(TraceAccess { tracer, remaining: len }))?;
+        self.shapes.push(Shape::Tuple(shapes));
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (value, mut shapes) = self.nested(|tracer| visitor.visit_map(TraceAccess { tracer, remaining: 1 }))?;
+        let value_shape = shapes.pop().unwrap_or(Shape::Unit);
+        let key_shape = shapes.pop().unwrap_or(Shape::Unit);
+        self.shapes.push(Shape::Map(Box::new(key_shape), Box::new(value_shape)));
+        Ok(value)
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Struct variants come through here without a name
+        if !name.is_empty() && self.struct_stack.contains(&name) {
+            return Err(unsupported(&format!("recursive type `{}`", name)));
+        }
+
+        self.struct_stack.push(name);
+        let (value, shapes) = self.nested(|tracer| {
+            visitor.visit_seq(TraceAccess {
+                tracer,
+                remaining: fields.len(),
+            })
+        })?;
+        self.struct_stack.pop();
+        self.shapes
+            .push(Shape::Struct(fields.iter().copied().zip(shapes).collect()));
+        Ok(value)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.enum_stack.contains(&name) {
+            return Err(unsupported(&format!("recursive type `{}`", name)));
+        }
+
+        let choice_of = |name: &str| self.enum_choices.get(name).copied().unwrap_or(0);
+        if !self.enum_variants.contains_key(name) {
+            self.enum_variants.insert(name, vec![None; variants.len()]);
+            let path = self
+                .enum_stack
+                .iter()
+                .map(|parent| (*parent, choice_of(parent)))
+                .collect();
+            self.enum_paths.push((name, path));
+        }
+        let choice = choice_of(name);
+
+        self.enum_stack.push(name);
+        let (value, mut shapes) = self.nested(|tracer| visitor.visit_enum(TraceEnum { tracer, choice }))?;
+        self.enum_stack.pop();
+
+        let variant_shape = shapes.pop().unwrap_or(Shape::Unit);
+        if let Some(variant) = self.enum_variants.get_mut(name).and_then(|v| v.get_mut(choice)) {
+            variant.get_or_insert((variants[choice], variant_shape));
+        }
+
+        self.shapes.push(Shape::Enum(name));
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(unsupported("identifier"))
+    }
+
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(unsupported("ignored data"))
+    }
+}
+
+struct TraceAccess<'a, 'b> {
+    tracer: &'a mut Tracer<'b>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for TraceAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.tracer).map(Some)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for TraceAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.tracer).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.tracer)
+    }
+}
+
+struct TraceEnum<'a, 'b> {
+    tracer: &'a mut Tracer<'b>,
+    choice: usize,
+}
+
+impl<'a, 'b, 'de> de::EnumAccess<'de> for TraceEnum<'a, 'b> {
+    type Error = Error;
+    type Variant = &'a mut Tracer<'b>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.choice as u32))?;
+        Ok((value, self.tracer))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Tracer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.shapes.push(Shape::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}